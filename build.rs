@@ -0,0 +1,152 @@
+use std::env;
+
+/// What the code can rely on for a QingKe core version
+struct Core {
+    /// Feature name, also the value of `cfg(qingke_core)`
+    name: &'static str,
+    /// `GINTENR` (CSR 0x800) masks interrupts, V3A has it but it does not work
+    has_gintenr: bool,
+    /// VTF interrupt numbers are set in `VTFIDR`, instead of the top byte of `VTFADDRRx`
+    has_vtf_id_register: bool,
+    /// Levels of hardware prologue/epilogue enabled by the runtime, the minimum of the family
+    hpe_depth: u8,
+    has_umode: bool,
+    /// Required alignment of the vector table (`mtvec` base)
+    vector_alignment: u32,
+    /// `mtvec` supports the vector table of handler addresses (mode 3)
+    has_vectored_address: bool,
+    /// Number of PMP entries, 0 without PMP
+    pmp_regions: u8,
+    /// Smallest PMP region in bytes, 0 without PMP
+    pmp_granularity: u32,
+}
+
+const CORES: &[Core] = &[
+    // V2A, V2C: CH32V003, CH32V00x
+    Core {
+        name: "v2",
+        has_gintenr: false,
+        has_vtf_id_register: true,
+        hpe_depth: 2,
+        has_umode: false,
+        vector_alignment: 1024,
+        has_vectored_address: true,
+        pmp_regions: 0,
+        pmp_granularity: 0,
+    },
+    // CH32V103, CH565, CH569, CH571, CH573
+    Core {
+        name: "v3a",
+        has_gintenr: false,
+        has_vtf_id_register: false,
+        hpe_depth: 0,
+        has_umode: true,
+        vector_alignment: 4,
+        has_vectored_address: false,
+        pmp_regions: 4,
+        pmp_granularity: 4,
+    },
+    Core {
+        name: "v3b",
+        has_gintenr: true,
+        has_vtf_id_register: false,
+        hpe_depth: 0,
+        has_umode: true,
+        vector_alignment: 4,
+        has_vectored_address: true,
+        pmp_regions: 4,
+        pmp_granularity: 4,
+    },
+    // V4A, V4B, V4C, V4F: CH32V20x, CH32V30x, CH32X035, CH32L103, CH58x, CH59x
+    Core {
+        name: "v4",
+        has_gintenr: true,
+        has_vtf_id_register: true,
+        hpe_depth: 2,
+        has_umode: true,
+        vector_alignment: 4,
+        has_vectored_address: true,
+        pmp_regions: 4,
+        pmp_granularity: 4,
+    },
+];
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+
+    let names: Vec<_> = CORES.iter().map(|c| format!("\"{}\"", c.name)).collect();
+    println!(
+        "cargo::rustc-check-cfg=cfg(qingke_core, values({}))",
+        names.join(", ")
+    );
+    println!("cargo::rustc-check-cfg=cfg(has_gintenr)");
+    println!("cargo::rustc-check-cfg=cfg(has_vtf_id_register)");
+    println!("cargo::rustc-check-cfg=cfg(hpe_depth, values(\"0\", \"2\", \"3\"))");
+    println!("cargo::rustc-check-cfg=cfg(has_umode)");
+    println!("cargo::rustc-check-cfg=cfg(vector_alignment, values(\"4\", \"1024\"))");
+    println!("cargo::rustc-check-cfg=cfg(has_vectored_address)");
+    println!("cargo::rustc-check-cfg=cfg(pmp_regions, values(\"0\", \"4\"))");
+    println!("cargo::rustc-check-cfg=cfg(pmp_granularity, values(\"0\", \"4\"))");
+
+    let selected: Vec<&Core> = CORES
+        .iter()
+        .filter(|c| env::var(format!("CARGO_FEATURE_{}", c.name.to_uppercase())).is_ok())
+        .collect();
+
+    let core = match selected[..] {
+        [core] => core,
+        [] if env::var("CARGO_FEATURE__V3").is_ok() => {
+            panic!("`_v3` is internal, select the QingKe core with `v3a` or `v3b`")
+        }
+        [] => panic!(
+            "Select the QingKe core with exactly one of the {} features",
+            names.join(", ")
+        ),
+        _ => {
+            let names: Vec<_> = selected.iter().map(|c| c.name).collect();
+            panic!(
+                "Only one QingKe core can be selected, got {}",
+                names.join(", ")
+            )
+        }
+    };
+
+    println!("cargo::rustc-cfg=qingke_core=\"{}\"", core.name);
+    println!("cargo::rustc-cfg=hpe_depth=\"{}\"", core.hpe_depth);
+    println!(
+        "cargo::rustc-cfg=vector_alignment=\"{}\"",
+        core.vector_alignment
+    );
+    println!("cargo::rustc-cfg=pmp_regions=\"{}\"", core.pmp_regions);
+    println!(
+        "cargo::rustc-cfg=pmp_granularity=\"{}\"",
+        core.pmp_granularity
+    );
+    for (cfg, enabled) in [
+        ("has_gintenr", core.has_gintenr),
+        ("has_vtf_id_register", core.has_vtf_id_register),
+        ("has_umode", core.has_umode),
+        ("has_vectored_address", core.has_vectored_address),
+    ] {
+        if enabled {
+            println!("cargo::rustc-cfg={cfg}");
+        }
+    }
+
+    // Exposed to the build scripts of dependents as `DEP_QINGKE_<KEY>`
+    println!("cargo::metadata=core={}", core.name);
+    println!("cargo::metadata=has_gintenr={}", core.has_gintenr);
+    println!(
+        "cargo::metadata=has_vtf_id_register={}",
+        core.has_vtf_id_register
+    );
+    println!("cargo::metadata=hpe_depth={}", core.hpe_depth);
+    println!("cargo::metadata=has_umode={}", core.has_umode);
+    println!("cargo::metadata=vector_alignment={}", core.vector_alignment);
+    println!(
+        "cargo::metadata=has_vectored_address={}",
+        core.has_vectored_address
+    );
+    println!("cargo::metadata=pmp_regions={}", core.pmp_regions);
+    println!("cargo::metadata=pmp_granularity={}", core.pmp_granularity);
+}
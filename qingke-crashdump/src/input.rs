@@ -0,0 +1,150 @@
+//! Record bytes from a raw dump or a hex dump
+use anyhow::{Context, Result, bail};
+
+/// Bytes of `data`, decoded as hex text unless `raw` is set or it is not text
+///
+/// Hex text is split in whitespace separated tokens. Tokens ending with `:` are
+/// addresses and skipped, as is everything after a `|` (the ASCII column of
+/// `hexdump -C`) and the offset column of `hexdump -C`. Other tokens are bytes, in
+/// memory order, or with `words` little-endian 32-bit words, as printed by debugger
+/// memory reads. `0x` prefixes are accepted.
+///
+/// A `*` line of `hexdump -C` repeats the line before it up to the next offset, the
+/// line with only the final offset is skipped.
+pub fn decode(data: &[u8], raw: bool, words: bool) -> Result<Vec<u8>> {
+    if raw {
+        return Ok(data.to_vec());
+    }
+    let Ok(text) = std::str::from_utf8(data) else {
+        return Ok(data.to_vec());
+    };
+
+    let mut bytes = Vec::new();
+    // offset after the last `hexdump -C` line and its bytes, for `*` lines
+    let mut hexdump: Option<(usize, Vec<u8>)> = None;
+    let mut repeat = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('|').next().unwrap_or_default();
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        if hexdump.is_some() && tokens == ["*"] {
+            repeat = true;
+            continue;
+        }
+
+        let offset = if words {
+            None
+        } else {
+            hexdump_offset(&tokens, hexdump.is_some())
+        };
+        if let Some(offset) = offset {
+            tokens.remove(0);
+            if let Some((end, last)) = hexdump.as_ref().filter(|_| repeat) {
+                let mut end = *end;
+                while end < offset && !last.is_empty() {
+                    let len = last.len().min(offset - end);
+                    bytes.extend_from_slice(&last[..len]);
+                    end += len;
+                }
+            }
+            repeat = false;
+        }
+
+        let start = bytes.len();
+        for token in &tokens {
+            if token.ends_with(':') {
+                continue;
+            }
+            let digits = token.trim_start_matches("0x").trim_start_matches("0X");
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                bail!("line {}: `{token}` is not hex", number + 1);
+            }
+            if words {
+                let word = u32::from_str_radix(digits, 16).with_context(|| {
+                    format!("line {}: `{token}` is not a 32-bit word", number + 1)
+                })?;
+                bytes.extend_from_slice(&word.to_le_bytes());
+            } else {
+                if digits.len() % 2 != 0 {
+                    bail!("line {}: `{token}` has an odd number of digits", number + 1);
+                }
+                for i in (0..digits.len()).step_by(2) {
+                    bytes.push(u8::from_str_radix(&digits[i..i + 2], 16)?);
+                }
+            }
+        }
+        if let Some(offset) = offset {
+            hexdump = Some((offset + bytes.len() - start, bytes[start..].to_vec()));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Offset of a `hexdump -C` line: 8 digits, then single bytes, or alone on the last
+/// line of a dump
+fn hexdump_offset(tokens: &[&str], in_hexdump: bool) -> Option<usize> {
+    let (offset, rest) = tokens.split_first()?;
+    let is_line = if rest.is_empty() {
+        in_hexdump
+    } else {
+        rest.iter().all(|token| token.len() == 2)
+    };
+    if offset.len() != 8 || !is_line {
+        return None;
+    }
+    usize::from_str_radix(offset, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn raw() {
+        let data = [0x51, 0x4b, 0x43, 0x52, 0xff, 0x00];
+        assert_eq!(decode(&data, false, false).unwrap(), data);
+        // valid text, taken as is
+        assert_eq!(decode(b"0a 0b", true, false).unwrap(), b"0a 0b");
+    }
+
+    #[test]
+    fn xxd_plain() {
+        let text = "514b435201000000\n0200\n";
+        assert_eq!(
+            decode(text.as_bytes(), false, false).unwrap(),
+            [0x51, 0x4b, 0x43, 0x52, 1, 0, 0, 0, 2, 0]
+        );
+    }
+
+    #[test]
+    fn words() {
+        let text = "0x20000100: 0x5243534b 00000001\n";
+        assert_eq!(
+            decode(text.as_bytes(), false, true).unwrap(),
+            [0x4b, 0x53, 0x43, 0x52, 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn hexdump_repeated_lines() {
+        let text = "\
+00000000  51 4b 43 52 01 00 00 00  00 00 00 00 02 00 00 00  |QKCR............|
+00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000040  aa bb                                             |..|
+00000042
+";
+        let bytes = decode(text.as_bytes(), false, false).unwrap();
+        assert_eq!(bytes.len(), 0x42);
+        assert_eq!(&bytes[..4], b"QKCR");
+        assert!(bytes[0x10..0x40].iter().all(|&b| b == 0));
+        assert_eq!(&bytes[0x40..], [0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn not_hex() {
+        assert!(decode(b"51 4g", false, false).is_err());
+        assert!(decode(b"514", false, false).is_err());
+        // `*` is only a repeat after a `hexdump -C` line
+        assert!(decode(b"51 4b\n*\n", false, false).is_err());
+    }
+}
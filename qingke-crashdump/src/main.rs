@@ -0,0 +1,121 @@
+//! Decodes a crash record of `qingke-rt`'s `crash-record` feature into a readable report
+use std::io::Read;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+mod input;
+mod record;
+mod symbols;
+
+use record::{CrashRecord, Kind};
+use symbols::Symbols;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Record bytes: a raw dump, or a hex dump (UART log, `hexdump -C`, debugger memory
+    /// read). `-` reads stdin
+    record: PathBuf,
+
+    /// Firmware ELF with debug info, to symbolize `mepc`, `ra` and the stack
+    #[arg(short, long)]
+    elf: Option<PathBuf>,
+
+    /// The record file is binary, even if it looks like text
+    #[arg(long, conflicts_with = "words")]
+    raw: bool,
+
+    /// Hex dump tokens are little-endian 32-bit words instead of bytes
+    #[arg(long)]
+    words: bool,
+
+    /// Offset of the record in the decoded bytes
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let data = if args.record.as_os_str() == "-" {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .context("reading stdin")?;
+        data
+    } else {
+        std::fs::read(&args.record).with_context(|| format!("reading {}", args.record.display()))?
+    };
+    let bytes = input::decode(&data, args.raw, args.words)?;
+    let bytes = bytes
+        .get(args.offset..)
+        .context("--offset is past the end of the record bytes")?;
+    let record = CrashRecord::parse(bytes)?;
+
+    let symbols = args.elf.as_deref().map(Symbols::load).transpose()?;
+
+    report(&record, symbols.as_ref());
+    Ok(())
+}
+
+fn report(record: &CrashRecord, symbols: Option<&Symbols>) {
+    let describe = |address: u32| {
+        symbols
+            .and_then(|symbols| symbols.describe(address))
+            .map(|description| format!("  {description}"))
+            .unwrap_or_default()
+    };
+
+    println!(
+        "QingKe crash record v{}, checksum {}",
+        record::VERSION,
+        if record.checksum_ok {
+            "ok"
+        } else {
+            "MISMATCH, fields may be corrupted"
+        }
+    );
+
+    match record.kind {
+        Kind::Exception => {
+            println!("kind:    exception");
+            println!(
+                "mcause:  {:#010x}  {}",
+                record.mcause,
+                record::cause_name(record.mcause)
+            );
+            println!("mepc:    {:#010x}{}", record.mepc, describe(record.mepc));
+            println!("mtval:   {:#010x}", record.mtval);
+        }
+        Kind::Panic => println!("kind:    panic"),
+        Kind::StackOverflow => {
+            println!("kind:    stack overflow");
+            println!("pc:      {:#010x}{}", record.mepc, describe(record.mepc));
+        }
+        Kind::Unknown(kind) => println!("kind:    unknown ({kind})"),
+    }
+    // only exceptions save `ra`
+    if !matches!(record.kind, Kind::Panic | Kind::StackOverflow) {
+        println!("ra:      {:#010x}{}", record.ra, describe(record.ra));
+    }
+    println!("sp:      {:#010x}", record.sp);
+    println!(
+        "GISR:    {:#010x}  {}",
+        record.gisr,
+        record::gisr_description(record.gisr)
+    );
+
+    if !record.message.is_empty() {
+        println!();
+        println!("{}", record.message);
+    }
+
+    println!();
+    println!("stack:");
+    for (i, word) in record.stack.iter().enumerate() {
+        let address = record.sp.wrapping_add(4 * i as u32);
+        println!("  {address:#010x}: {word:#010x}{}", describe(*word));
+    }
+}
@@ -0,0 +1,237 @@
+//! Layout of the crash record written by `qingke_rt::crash`, version 1
+use anyhow::{Result, bail, ensure};
+
+/// `"QKCR"`
+pub const MAGIC: u32 = 0x5243_4b51;
+pub const VERSION: u32 = 1;
+pub const STACK_WORDS: usize = 16;
+pub const MESSAGE_LEN: usize = 128;
+/// Size of a version 1 record in bytes
+pub const SIZE: usize = 4 * 9 + 4 * STACK_WORDS + 4 + MESSAGE_LEN + 4;
+
+const CHECKSUM_OFFSET: usize = SIZE - 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Exception,
+    Panic,
+    StackOverflow,
+    Unknown(u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct CrashRecord {
+    pub kind: Kind,
+    pub mcause: u32,
+    pub mepc: u32,
+    pub mtval: u32,
+    pub sp: u32,
+    pub ra: u32,
+    pub gisr: u32,
+    pub stack: [u32; STACK_WORDS],
+    pub message: String,
+    /// Whether the CRC-32 matches, a record with a bad one is still decoded
+    pub checksum_ok: bool,
+}
+
+impl CrashRecord {
+    /// Decode a record from its first byte, trailing bytes are ignored
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= SIZE,
+            "a crash record is {SIZE} bytes, got {}",
+            bytes.len()
+        );
+        let bytes = &bytes[..SIZE];
+        let word =
+            |index: usize| u32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap());
+
+        let magic = word(0);
+        if magic != MAGIC {
+            bail!("no crash record: magic is {magic:#010x}, expected {MAGIC:#010x} (\"QKCR\")");
+        }
+        let version = word(1);
+        ensure!(
+            version == VERSION,
+            "crash record version {version} is not supported, only {VERSION}"
+        );
+
+        let kind = match word(2) {
+            0 => Kind::Exception,
+            1 => Kind::Panic,
+            2 => Kind::StackOverflow,
+            other => Kind::Unknown(other),
+        };
+
+        let message_len = (word(9 + STACK_WORDS) as usize).min(MESSAGE_LEN);
+        let message_start = 4 * (10 + STACK_WORDS);
+        let message = &bytes[message_start..message_start + message_len];
+
+        let checksum = u32::from_le_bytes(bytes[CHECKSUM_OFFSET..].try_into().unwrap());
+
+        Ok(CrashRecord {
+            kind,
+            mcause: word(3),
+            mepc: word(4),
+            mtval: word(5),
+            sp: word(6),
+            ra: word(7),
+            gisr: word(8),
+            stack: std::array::from_fn(|i| word(9 + i)),
+            message: String::from_utf8_lossy(message).into_owned(),
+            checksum_ok: crc32(&bytes[..CHECKSUM_OFFSET]) == checksum,
+        })
+    }
+}
+
+/// CRC-32 (IEEE), as computed by the firmware
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Name of an `mcause` value: `__EXCEPTIONS` entries for exceptions, `CoreInterrupt`
+/// variants or the external interrupt number for interrupts
+pub fn cause_name(mcause: u32) -> String {
+    let code = mcause & 0x7fff_ffff;
+    if mcause & 0x8000_0000 == 0 {
+        let name = match code {
+            0 => "InstructionMisaligned",
+            1 => "InstructionFault",
+            2 => "IllegalInstruction",
+            3 => "Breakpoint",
+            4 => "LoadMisaligned",
+            5 => "LoadFault",
+            6 => "StoreMisaligned",
+            7 => "StoreFault",
+            8 => "UserEnvCall",
+            11 => "MachineEnvCall",
+            _ => return format!("exception {code}"),
+        };
+        name.to_string()
+    } else {
+        let name = match code {
+            2 => "NonMaskableInt",
+            3 => "Exception",
+            5 => "MachineEnvCall",
+            8 => "UserEnvCall",
+            9 => "Breakpoint",
+            12 => "SysTick",
+            14 => "Software",
+            _ => return format!("interrupt {code}"),
+        };
+        format!("interrupt {code}, {name}")
+    }
+}
+
+/// `PFIC_GISR`: nesting state in bits 0 to 7, one bit per level, then active and pending
+pub fn gisr_description(gisr: u32) -> String {
+    let levels = (gisr & 0xff).count_ones();
+    let mut description = match levels {
+        0 => "not in an interrupt".to_string(),
+        1 => "in an interrupt".to_string(),
+        n => format!("{n} nested interrupts"),
+    };
+    if gisr & (1 << 8) != 0 {
+        description.push_str(", active");
+    }
+    if gisr & (1 << 9) != 0 {
+        description.push_str(", pending");
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A panic record with `message`, its CRC-32 fixed up
+    fn image(message: &str) -> Vec<u8> {
+        let mut words = vec![MAGIC, VERSION, 1, 0, 0, 0, 0x2000_4f00, 0x0c, 0x101];
+        words.extend((0..STACK_WORDS as u32).map(|i| 0x100 + i));
+        words.push(message.len() as u32);
+        let mut bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let mut buffer = [0; MESSAGE_LEN];
+        buffer[..message.len()].copy_from_slice(message.as_bytes());
+        bytes.extend_from_slice(&buffer);
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn parse() {
+        let mut bytes = image("src/main.rs:12:5: index out of bounds");
+        assert_eq!(bytes.len(), SIZE);
+        bytes.extend_from_slice(&[0xff; 8]);
+
+        let record = CrashRecord::parse(&bytes).unwrap();
+        assert!(record.checksum_ok);
+        assert_eq!(record.kind, Kind::Panic);
+        assert_eq!(record.sp, 0x2000_4f00);
+        assert_eq!(record.ra, 0x0c);
+        assert_eq!(record.gisr, 0x101);
+        assert_eq!(record.stack[0], 0x100);
+        assert_eq!(
+            record.stack[STACK_WORDS - 1],
+            0x100 + STACK_WORDS as u32 - 1
+        );
+        assert_eq!(record.message, "src/main.rs:12:5: index out of bounds");
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = image("");
+        bytes[0] ^= 1;
+        assert!(CrashRecord::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn bad_version() {
+        let mut bytes = image("");
+        bytes[4] = 2;
+        assert!(CrashRecord::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut bytes = image("");
+        bytes[16] ^= 1;
+        assert!(!CrashRecord::parse(&bytes).unwrap().checksum_ok);
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(CrashRecord::parse(&image("")[..SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn cause_names() {
+        assert_eq!(cause_name(2), "IllegalInstruction");
+        assert_eq!(cause_name(11), "MachineEnvCall");
+        assert_eq!(cause_name(10), "exception 10");
+        assert_eq!(cause_name(0x8000_000c), "interrupt 12, SysTick");
+        assert_eq!(cause_name(0x8000_0030), "interrupt 48");
+    }
+
+    #[test]
+    fn gisr() {
+        assert_eq!(gisr_description(0), "not in an interrupt");
+        assert_eq!(gisr_description(0x101), "in an interrupt, active");
+        assert_eq!(
+            gisr_description(0x303),
+            "2 nested interrupts, active, pending"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Addresses to functions and source lines, from the firmware ELF
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use object::{Object, ObjectSection, SectionFlags, SectionKind};
+
+pub struct Symbols {
+    loader: addr2line::Loader,
+    /// Executable sections, only addresses inside them are looked up
+    code: Vec<Range<u64>>,
+}
+
+impl Symbols {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let elf =
+            object::File::parse(&*data).with_context(|| format!("parsing {}", path.display()))?;
+        let code = elf
+            .sections()
+            .filter(|section| {
+                section.kind() == SectionKind::Text
+                    || matches!(section.flags(), SectionFlags::Elf { sh_flags } if sh_flags & u64::from(object::elf::SHF_EXECINSTR) != 0)
+            })
+            .map(|section| section.address()..section.address() + section.size())
+            .collect();
+        let loader = addr2line::Loader::new(path)
+            .map_err(|e| anyhow!("loading debug info of {}: {e}", path.display()))?;
+        Ok(Symbols { loader, code })
+    }
+
+    pub fn is_code(&self, address: u32) -> bool {
+        self.code
+            .iter()
+            .any(|range| range.contains(&u64::from(address)))
+    }
+
+    /// `function at file:line`, with the functions inlined at `address` first, or the
+    /// symbol name without debug info
+    pub fn describe(&self, address: u32) -> Option<String> {
+        if !self.is_code(address) {
+            return None;
+        }
+        let probe = u64::from(address);
+
+        let mut frames = Vec::new();
+        if let Ok(mut iter) = self.loader.find_frames(probe) {
+            while let Ok(Some(frame)) = iter.next() {
+                let function = frame
+                    .function
+                    .as_ref()
+                    .and_then(|f| f.demangle().ok())
+                    .map(|name| name.into_owned());
+                let location = frame
+                    .location
+                    .as_ref()
+                    .and_then(|l| Some(format!("{}:{}", l.file?, l.line?)));
+                match (function, location) {
+                    (Some(f), Some(l)) => frames.push(format!("{f} at {l}")),
+                    (Some(f), None) => frames.push(f),
+                    (None, Some(l)) => frames.push(format!("?? at {l}")),
+                    (None, None) => {}
+                }
+            }
+        }
+        if !frames.is_empty() {
+            return Some(frames.join("\n    inlined in "));
+        }
+
+        self.loader
+            .find_symbol(probe)
+            .map(|name| addr2line::demangle_auto(name.into(), None).into_owned())
+    }
+}
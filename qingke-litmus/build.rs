@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+use std::{env, fs};
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    if env::var("CARGO_FEATURE_QEMU").is_ok() {
+        // No qingke-rt in the image, bring our own script
+        fs::write(out_dir.join("link.x"), include_bytes!("qemu.x")).unwrap();
+    } else {
+        // qingke-rt's link.x includes these, there is no PAC to provide `device.x`
+        fs::write(out_dir.join("memory.x"), include_bytes!("memory.x")).unwrap();
+        fs::write(out_dir.join("device.x"), b"").unwrap();
+    }
+
+    println!("cargo:rustc-link-search={}", out_dir.display());
+
+    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=qemu.x");
+    println!("cargo:rerun-if-changed=build.rs");
+}
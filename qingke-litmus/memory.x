@@ -0,0 +1,13 @@
+/* Smallest common layout of the V3/V4 parts, adjust for your chip */
+MEMORY
+{
+    FLASH : ORIGIN = 0x00000000, LENGTH = 64K
+    RAM : ORIGIN = 0x20000000, LENGTH = 20K
+}
+
+REGION_ALIAS("REGION_TEXT", FLASH);
+REGION_ALIAS("REGION_RODATA", FLASH);
+REGION_ALIAS("REGION_DATA", RAM);
+REGION_ALIAS("REGION_BSS", RAM);
+REGION_ALIAS("REGION_HEAP", RAM);
+REGION_ALIAS("REGION_STACK", RAM);
@@ -0,0 +1,50 @@
+/* QEMU `virt` machine, loaded with `-bios none -kernel` */
+MEMORY
+{
+    RAM : ORIGIN = 0x80000000, LENGTH = 128K
+}
+
+ENTRY(_start)
+
+SECTIONS
+{
+    .text :
+    {
+        KEEP(*(.init));
+        *(.trap .trap.*);
+        *(.text .text.*);
+    } >RAM
+
+    .rodata : ALIGN(4)
+    {
+        *(.srodata .srodata.*);
+        *(.rodata .rodata.*);
+        . = ALIGN(4);
+    } >RAM
+
+    .data : ALIGN(4)
+    {
+        PROVIDE( __global_pointer$ = . + 0x800 );
+        *(.sdata .sdata.* .sdata2 .sdata2.*);
+        *(.data .data.*);
+        . = ALIGN(4);
+    } >RAM
+
+    .bss (NOLOAD) : ALIGN(4)
+    {
+        PROVIDE( _sbss = .);
+        *(.sbss .sbss.* .bss .bss.*);
+        . = ALIGN(4);
+        PROVIDE( _ebss = .);
+    } >RAM
+
+    .uninit (NOLOAD) : ALIGN(4)
+    {
+        *(.uninit .uninit.*);
+    } >RAM
+
+    PROVIDE(_stack_top = ORIGIN(RAM) + LENGTH(RAM));
+
+    .eh_frame (INFO) : { KEEP(*(.eh_frame)) }
+    .eh_frame_hdr (INFO) : { *(.eh_frame_hdr) }
+}
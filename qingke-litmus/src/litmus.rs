@@ -0,0 +1,670 @@
+//! The tests and the software interrupt hook they drive
+//!
+//! Each test runs a short instruction sequence against a scratch word and
+//! compares the result with what the RISC-V A extension requires. Behaviour
+//! the ISA leaves to the implementation is recorded as `Observed` so the
+//! report can be compared against a reference run.
+use core::arch::asm;
+use core::ptr;
+
+use crate::report::Report;
+
+/// A store that pends the software interrupt
+#[derive(Clone, Copy)]
+pub struct Trigger {
+    pub addr: *mut u32,
+    pub value: u32,
+}
+
+impl Trigger {
+    #[inline(always)]
+    fn fire(&self) {
+        unsafe { ptr::write_volatile(self.addr, self.value) };
+    }
+}
+
+/// What the board provides to run the tests
+pub trait Platform {
+    fn name(&self) -> &'static str;
+    /// The store pending the software interrupt, `None` skips the interrupt tests
+    fn swi_trigger(&self) -> Option<Trigger>;
+    fn enable_swi(&mut self);
+    fn disable_swi(&mut self);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail {
+        expected: u32,
+        observed: u32,
+    },
+    /// Implementation-defined behaviour, recorded for reference
+    Observed(u32),
+    Skipped(&'static str),
+}
+
+#[derive(Default, Debug)]
+pub struct Summary {
+    pub passed: u32,
+    pub failed: u32,
+    pub observed: u32,
+    pub skipped: u32,
+}
+
+struct Test {
+    name: &'static str,
+    run: fn(&mut dyn Platform) -> Outcome,
+}
+
+const TESTS: &[Test] = &[
+    Test {
+        name: "amoswap.w",
+        run: amoswap,
+    },
+    Test {
+        name: "amoadd.w",
+        run: amoadd,
+    },
+    Test {
+        name: "amoand/or/xor.w",
+        run: amo_logic,
+    },
+    Test {
+        name: "amomin/max.w",
+        run: amo_minmax,
+    },
+    Test {
+        name: "amominu/maxu.w",
+        run: amo_minmax_unsigned,
+    },
+    Test {
+        name: "amoadd.w.aqrl",
+        run: amoadd_aqrl,
+    },
+    Test {
+        name: "lr/sc",
+        run: lr_sc,
+    },
+    Test {
+        name: "sc without reservation",
+        run: sc_without_reservation,
+    },
+    Test {
+        name: "sc after sc",
+        run: sc_after_sc,
+    },
+    Test {
+        name: "sc to other address",
+        run: sc_other_address,
+    },
+    Test {
+        name: "sc after plain store",
+        run: sc_after_store,
+    },
+    Test {
+        name: "swi taken",
+        run: swi_taken,
+    },
+    Test {
+        name: "sc after isr with sc",
+        run: sc_after_isr_sc,
+    },
+    Test {
+        name: "sc after isr without sc",
+        run: sc_after_isr_nop,
+    },
+    Test {
+        name: "lr result across isr",
+        run: lr_result_across_isr,
+    },
+    Test {
+        name: "amo result across isr",
+        run: amo_result_across_isr,
+    },
+    Test {
+        name: "amoadd vs isr amoadd",
+        run: amo_stress,
+    },
+    Test {
+        name: "lr/sc vs isr lr/sc",
+        run: lrsc_stress,
+    },
+    Test {
+        name: "lr/sc vs isr amoadd",
+        run: lrsc_vs_amo_stress,
+    },
+];
+
+/// Run every test, reporting as we go
+pub fn run(platform: &mut dyn Platform, report: &mut dyn Report) -> Summary {
+    let mut summary = Summary::default();
+
+    report.begin(platform.name(), TESTS.len());
+    for test in TESTS {
+        platform.disable_swi();
+        set_swi_action(SwiAction::Nop);
+
+        let outcome = (test.run)(platform);
+
+        platform.disable_swi();
+        match outcome {
+            Outcome::Pass => summary.passed += 1,
+            Outcome::Fail { .. } => summary.failed += 1,
+            Outcome::Observed(_) => summary.observed += 1,
+            Outcome::Skipped(_) => summary.skipped += 1,
+        }
+        report.result(test.name, &outcome);
+    }
+    report.end(&summary);
+
+    summary
+}
+
+macro_rules! amo {
+    ($insn:literal, $addr:expr, $value:expr) => {{
+        let old: u32;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +a",
+                concat!($insn, " {old}, {value}, ({addr})"),
+                ".option pop",
+                old = out(reg) old,
+                value = in(reg) $value,
+                addr = in(reg) $addr,
+                options(nostack),
+            )
+        };
+        old
+    }};
+}
+
+// ---- software interrupt hook ----
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SwiAction {
+    Nop,
+    /// A lone `sc.w` to another word, like a trap handler dropping the reservation
+    Sc,
+    AmoAdd,
+    LrScAdd,
+    /// Overwrite all caller-saved registers
+    Clobber,
+}
+
+static mut SWI_ACTION: SwiAction = SwiAction::Nop;
+static mut SWI_COUNT: u32 = 0;
+static mut SWI_EPC: usize = 0;
+
+fn set_swi_action(action: SwiAction) {
+    unsafe {
+        ptr::write_volatile(&raw mut SWI_ACTION, action);
+        ptr::write_volatile(&raw mut SWI_COUNT, 0);
+        ptr::write_volatile(&raw mut SWI_EPC, 0);
+    }
+}
+
+fn swi_count() -> u32 {
+    unsafe { ptr::read_volatile(&raw const SWI_COUNT) }
+}
+
+fn swi_epc() -> usize {
+    unsafe { ptr::read_volatile(&raw const SWI_EPC) }
+}
+
+/// Called by the platform's software interrupt handler with the interrupted pc
+pub fn on_swi(epc: usize) {
+    let target = word(0);
+    match unsafe { ptr::read_volatile(&raw const SWI_ACTION) } {
+        SwiAction::Nop => {}
+        SwiAction::Sc => {
+            sc(word(DISTANT), 0);
+        }
+        SwiAction::AmoAdd => {
+            amo!("amoadd.w", target, 1u32);
+        }
+        SwiAction::LrScAdd => lr_sc_add(target),
+        SwiAction::Clobber => unsafe {
+            asm!(
+                "li t0, 0xdeadbeef",
+                "mv t1, t0", "mv t2, t0", "mv t3, t0", "mv t4, t0", "mv t5, t0", "mv t6, t0",
+                "mv a0, t0", "mv a1, t0", "mv a2, t0", "mv a3, t0",
+                "mv a4, t0", "mv a5, t0", "mv a6, t0", "mv a7, t0",
+                out("t0") _, out("t1") _, out("t2") _, out("t3") _,
+                out("t4") _, out("t5") _, out("t6") _,
+                out("a0") _, out("a1") _, out("a2") _, out("a3") _,
+                out("a4") _, out("a5") _, out("a6") _, out("a7") _,
+                options(nomem, nostack),
+            )
+        },
+    }
+    unsafe {
+        ptr::write_volatile(&raw mut SWI_EPC, epc);
+        ptr::write_volatile(&raw mut SWI_COUNT, swi_count() + 1);
+    }
+}
+
+/// Give a pended interrupt time to be taken
+fn settle() {
+    for _ in 0..64 {
+        unsafe { asm!("nop") };
+    }
+}
+
+// ---- instruction helpers ----
+
+/// Words far enough apart to sit in different reservation sets
+const DISTANT: usize = 64;
+static mut SCRATCH: [u32; 2 * DISTANT] = [0; 2 * DISTANT];
+
+fn word(index: usize) -> *mut u32 {
+    (&raw mut SCRATCH).cast::<u32>().wrapping_add(index)
+}
+
+fn scratch(value: u32) -> *mut u32 {
+    let p = word(0);
+    unsafe {
+        ptr::write_volatile(p, value);
+        ptr::write_volatile(word(DISTANT), value);
+    }
+    p
+}
+
+fn load(p: *const u32) -> u32 {
+    unsafe { ptr::read_volatile(p) }
+}
+
+/// Returns 0 on success
+fn sc(addr: *mut u32, value: u32) -> u32 {
+    let res: u32;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "sc.w {res}, {value}, ({addr})",
+            ".option pop",
+            res = out(reg) res,
+            value = in(reg) value,
+            addr = in(reg) addr,
+            options(nostack),
+        )
+    };
+    res
+}
+
+fn lr_sc_add(addr: *mut u32) {
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "1:",
+            "lr.w {tmp}, ({addr})",
+            "addi {tmp}, {tmp}, 1",
+            "sc.w {tmp}, {tmp}, ({addr})",
+            "bnez {tmp}, 1b",
+            ".option pop",
+            tmp = out(reg) _,
+            addr = in(reg) addr,
+            options(nostack),
+        )
+    };
+}
+
+fn check(expected: u32, observed: u32) -> Outcome {
+    if expected == observed {
+        Outcome::Pass
+    } else {
+        Outcome::Fail { expected, observed }
+    }
+}
+
+/// Chain checks, stopping at the first failure
+macro_rules! check_all {
+    ($(($expected:expr, $observed:expr)),+ $(,)?) => {{
+        $(
+            if let o @ Outcome::Fail { .. } = check($expected, $observed) {
+                return o;
+            }
+        )+
+        Outcome::Pass
+    }};
+}
+
+// ---- single-hart semantics ----
+
+fn amoswap(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(0x1234_5678);
+    let old = amo!("amoswap.w", p, 0x9abc_def0u32);
+    check_all!((0x1234_5678, old), (0x9abc_def0, load(p)))
+}
+
+fn amoadd(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(0xffff_fffe);
+    let old = amo!("amoadd.w", p, 3u32);
+    check_all!((0xffff_fffe, old), (1, load(p)))
+}
+
+fn amo_logic(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(0b1100);
+    let and = amo!("amoand.w", p, 0b1010u32);
+    let and_result = load(p);
+    let or = amo!("amoor.w", p, 0b0101u32);
+    let or_result = load(p);
+    let xor = amo!("amoxor.w", p, 0b1111u32);
+    check_all!(
+        (0b1100, and),
+        (0b1000, and_result),
+        (0b1000, or),
+        (0b1101, or_result),
+        (0b1101, xor),
+        (0b0010, load(p)),
+    )
+}
+
+fn amo_minmax(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(5);
+    amo!("amomin.w", p, -3i32 as u32);
+    let min = load(p);
+    amo!("amomax.w", p, 7u32);
+    let max = load(p);
+    amo!("amomax.w", p, -1i32 as u32);
+    check_all!((-3i32 as u32, min), (7, max), (7, load(p)))
+}
+
+fn amo_minmax_unsigned(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(5);
+    amo!("amominu.w", p, -3i32 as u32);
+    let min = load(p);
+    amo!("amomaxu.w", p, -3i32 as u32);
+    let max = load(p);
+    amo!("amominu.w", p, 2u32);
+    check_all!((5, min), (-3i32 as u32, max), (2, load(p)))
+}
+
+fn amoadd_aqrl(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(10);
+    let a = amo!("amoadd.w.aq", p, 1u32);
+    let b = amo!("amoadd.w.rl", p, 1u32);
+    let c = amo!("amoadd.w.aqrl", p, 1u32);
+    check_all!((10, a), (11, b), (12, c), (13, load(p)))
+}
+
+fn lr_sc(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(41);
+    let (old, res): (u32, u32);
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "lr.w {old}, ({addr})",
+            "addi {new}, {old}, 1",
+            "sc.w {res}, {new}, ({addr})",
+            ".option pop",
+            old = out(reg) old,
+            new = out(reg) _,
+            res = out(reg) res,
+            addr = in(reg) p,
+            options(nostack),
+        )
+    };
+    check_all!((41, old), (0, res), (42, load(p)))
+}
+
+fn sc_without_reservation(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(1);
+    // drop any reservation left behind
+    sc(word(DISTANT), 0);
+    let res = sc(p, 2);
+    check_all!((1, (res != 0) as u32), (1, load(p)))
+}
+
+fn sc_after_sc(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(1);
+    let (first, second): (u32, u32);
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "lr.w {tmp}, ({addr})",
+            "sc.w {first}, {two}, ({addr})",
+            "sc.w {second}, {three}, ({addr})",
+            ".option pop",
+            tmp = out(reg) _,
+            first = out(reg) first,
+            second = out(reg) second,
+            two = in(reg) 2u32,
+            three = in(reg) 3u32,
+            addr = in(reg) p,
+            options(nostack),
+        )
+    };
+    check_all!((0, first), (1, (second != 0) as u32), (2, load(p)))
+}
+
+fn sc_other_address(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(1);
+    let other = word(DISTANT);
+    let res: u32;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "lr.w {tmp}, ({addr})",
+            "sc.w {res}, {two}, ({other})",
+            ".option pop",
+            tmp = out(reg) _,
+            res = out(reg) res,
+            two = in(reg) 2u32,
+            addr = in(reg) p,
+            other = in(reg) other,
+            options(nostack),
+        )
+    };
+    check_all!((1, (res != 0) as u32), (1, load(other)))
+}
+
+/// A store from the same hart may or may not break the reservation
+fn sc_after_store(_: &mut dyn Platform) -> Outcome {
+    let p = scratch(1);
+    let res: u32;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "lr.w {tmp}, ({addr})",
+            "sw {two}, 0({addr})",
+            "sc.w {res}, {three}, ({addr})",
+            ".option pop",
+            tmp = out(reg) _,
+            res = out(reg) res,
+            two = in(reg) 2u32,
+            three = in(reg) 3u32,
+            addr = in(reg) p,
+            options(nostack),
+        )
+    };
+    Outcome::Observed(res)
+}
+
+// ---- interaction with interrupts and the hardware stack ----
+
+fn swi_taken(platform: &mut dyn Platform) -> Outcome {
+    let Some(trigger) = platform.swi_trigger() else {
+        return Outcome::Skipped("no software interrupt");
+    };
+    platform.enable_swi();
+    trigger.fire();
+    settle();
+    check(1, swi_count())
+}
+
+struct Window {
+    /// Value loaded by `lr.w`
+    loaded: u32,
+    /// `sc.w` result, 0 on success
+    res: u32,
+    /// Whether the interrupt was taken between `lr.w` and `sc.w`
+    hit: bool,
+}
+
+/// Pend the software interrupt right before an `lr.w`/`sc.w` pair spaced by
+/// a constrained run of `nop`s, and locate where the interrupt was taken
+fn lr_sc_window(platform: &mut dyn Platform, action: SwiAction) -> Option<Window> {
+    let trigger = platform.swi_trigger()?;
+    let p = scratch(1);
+    set_swi_action(action);
+    platform.enable_swi();
+
+    let (loaded, res, lr_pc, sc_pc): (u32, u32, usize, usize);
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "la {lr_pc}, 2f",
+            "la {sc_pc}, 3f",
+            "sw {tvalue}, 0({taddr})",
+            "2:",
+            "lr.w {loaded}, ({addr})",
+            ".rept 12",
+            "nop",
+            ".endr",
+            "3:",
+            "sc.w {res}, {two}, ({addr})",
+            ".option pop",
+            lr_pc = out(reg) lr_pc,
+            sc_pc = out(reg) sc_pc,
+            loaded = out(reg) loaded,
+            res = out(reg) res,
+            tvalue = in(reg) trigger.value,
+            taddr = in(reg) trigger.addr,
+            two = in(reg) 2u32,
+            addr = in(reg) p,
+            options(nostack),
+        )
+    };
+    settle();
+
+    let epc = swi_epc();
+    Some(Window {
+        loaded,
+        res,
+        hit: swi_count() == 1 && epc > lr_pc && epc <= sc_pc,
+    })
+}
+
+/// The trap handler's `sc.w` must make the interrupted `sc.w` fail
+fn sc_after_isr_sc(platform: &mut dyn Platform) -> Outcome {
+    match lr_sc_window(platform, SwiAction::Sc) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some(Window { hit: false, .. }) => Outcome::Skipped("interrupt missed the window"),
+        Some(w) => check(1, (w.res != 0) as u32),
+    }
+}
+
+/// `mret` may or may not clear the reservation
+fn sc_after_isr_nop(platform: &mut dyn Platform) -> Outcome {
+    match lr_sc_window(platform, SwiAction::Nop) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some(Window { hit: false, .. }) => Outcome::Skipped("interrupt missed the window"),
+        Some(w) => Outcome::Observed(w.res),
+    }
+}
+
+/// The hardware stack must restore the `lr.w` destination clobbered by the handler
+fn lr_result_across_isr(platform: &mut dyn Platform) -> Outcome {
+    match lr_sc_window(platform, SwiAction::Clobber) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some(Window { hit: false, .. }) => Outcome::Skipped("interrupt missed the window"),
+        Some(w) => check(1, w.loaded),
+    }
+}
+
+fn amo_result_across_isr(platform: &mut dyn Platform) -> Outcome {
+    let Some(trigger) = platform.swi_trigger() else {
+        return Outcome::Skipped("no software interrupt");
+    };
+    let p = scratch(1);
+    set_swi_action(SwiAction::Clobber);
+    platform.enable_swi();
+
+    let old: u32;
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +a",
+            "sw {tvalue}, 0({taddr})",
+            "amoswap.w {old}, {two}, ({addr})",
+            ".rept 12",
+            "nop",
+            ".endr",
+            ".option pop",
+            old = out(reg) old,
+            tvalue = in(reg) trigger.value,
+            taddr = in(reg) trigger.addr,
+            two = in(reg) 2u32,
+            addr = in(reg) p,
+            options(nostack),
+        )
+    };
+    settle();
+
+    check_all!((1, swi_count()), (1, old), (2, load(p)))
+}
+
+const STRESS_ROUNDS: u32 = 2000;
+
+/// Increment the scratch word `STRESS_ROUNDS` times while the handler also
+/// increments it, pending the interrupt at varying distances from the update.
+/// Returns the expected and the final count.
+fn stress(
+    platform: &mut dyn Platform,
+    action: SwiAction,
+    update: fn(*mut u32),
+) -> Option<(u32, u32)> {
+    let trigger = platform.swi_trigger()?;
+    let p = scratch(0);
+    set_swi_action(action);
+    platform.enable_swi();
+
+    for i in 0..STRESS_ROUNDS {
+        if i % 3 == 0 {
+            trigger.fire();
+            for _ in 0..(i % 8) {
+                unsafe { asm!("nop") };
+            }
+        }
+        update(p);
+    }
+    settle();
+    platform.disable_swi();
+
+    Some((STRESS_ROUNDS + swi_count(), load(p)))
+}
+
+fn amo_add(p: *mut u32) {
+    amo!("amoadd.w", p, 1u32);
+}
+
+fn amo_stress(platform: &mut dyn Platform) -> Outcome {
+    match stress(platform, SwiAction::AmoAdd, amo_add) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some((expected, observed)) => check(expected, observed),
+    }
+}
+
+fn lrsc_stress(platform: &mut dyn Platform) -> Outcome {
+    match stress(platform, SwiAction::LrScAdd, lr_sc_add) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some((expected, observed)) => check(expected, observed),
+    }
+}
+
+/// Only another hart's store is required to break a reservation, so the
+/// handler's AMO may be lost. Reports the number of lost updates.
+fn lrsc_vs_amo_stress(platform: &mut dyn Platform) -> Outcome {
+    match stress(platform, SwiAction::AmoAdd, lr_sc_add) {
+        None => Outcome::Skipped("no software interrupt"),
+        Some((expected, observed)) => Outcome::Observed(expected.wrapping_sub(observed)),
+    }
+}
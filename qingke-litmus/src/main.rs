@@ -0,0 +1,32 @@
+//! Litmus tests for the AMO and LR/SC instructions of QingKe cores
+//!
+//! Run this on a chip revision before trusting its atomics with
+//! `unsafe-trust-wch-atomics`, and compare the report with the one produced
+//! under QEMU (`--no-default-features --features qemu`).
+#![no_std]
+#![no_main]
+
+mod litmus;
+mod report;
+
+#[cfg_attr(feature = "qemu", path = "platform/qemu.rs")]
+#[cfg_attr(not(feature = "qemu"), path = "platform/qingke.rs")]
+mod platform;
+
+#[cfg(not(any(feature = "v3a", feature = "v3b", feature = "v4", feature = "qemu")))]
+compile_error!("Select the core under test with `v3a`, `v3b` or `v4`, or build for `qemu`");
+
+#[cfg(all(
+    feature = "qemu",
+    any(feature = "v3a", feature = "v3b", feature = "v4")
+))]
+compile_error!("`qemu` does not use qingke-rt, build it with `--no-default-features`");
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        // Prevent this from turning into a UDF instruction
+        // see rust-lang/rust#28728 for details
+        continue;
+    }
+}
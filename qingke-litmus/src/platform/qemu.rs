@@ -0,0 +1,165 @@
+//! Reference run on QEMU's `virt` machine
+//!
+//! ```text
+//! qemu-system-riscv32 -machine virt -bios none -nographic -kernel <elf>
+//! ```
+//!
+//! There is no hardware stack here, so the trap entry saves the caller-saved
+//! registers itself. The machine software interrupt is pended through the
+//! CLINT and the result is reported on the NS16550 UART.
+use core::arch::{asm, global_asm};
+use core::fmt;
+use core::ptr;
+
+use crate::litmus::{self, Platform, Trigger};
+use crate::report::TextReport;
+
+const CLINT_MSIP: *mut u32 = 0x0200_0000 as *mut u32;
+const UART0_THR: *mut u8 = 0x1000_0000 as *mut u8;
+const UART0_LSR: *const u8 = 0x1000_0005 as *const u8;
+/// SiFive test device, used to exit QEMU
+const TEST_FINISHER: *mut u32 = 0x0010_0000 as *mut u32;
+
+global_asm!(
+    r#"
+    .section .init, "ax"
+    .global _start
+_start:
+    .option push
+    .option norelax
+    la gp, __global_pointer$
+    .option pop
+    la sp, _stack_top
+
+    la a0, _sbss
+    la a1, _ebss
+    bgeu a0, a1, 2f
+1:
+    sw zero, (a0)
+    addi a0, a0, 4
+    bltu a0, a1, 1b
+2:
+    la t0, _trap
+    csrw mtvec, t0
+    li t0, 0x8 // mstatus.MIE
+    csrs mstatus, t0
+    jal qemu_main
+
+    .section .trap, "ax"
+    .align 2
+_trap:
+    addi sp, sp, -64
+    sw ra, 0(sp)
+    sw t0, 4(sp)
+    sw t1, 8(sp)
+    sw t2, 12(sp)
+    sw a0, 16(sp)
+    sw a1, 20(sp)
+    sw a2, 24(sp)
+    sw a3, 28(sp)
+    sw a4, 32(sp)
+    sw a5, 36(sp)
+    sw a6, 40(sp)
+    sw a7, 44(sp)
+    sw t3, 48(sp)
+    sw t4, 52(sp)
+    sw t5, 56(sp)
+    sw t6, 60(sp)
+    jal qemu_trap
+    lw ra, 0(sp)
+    lw t0, 4(sp)
+    lw t1, 8(sp)
+    lw t2, 12(sp)
+    lw a0, 16(sp)
+    lw a1, 20(sp)
+    lw a2, 24(sp)
+    lw a3, 28(sp)
+    lw a4, 32(sp)
+    lw a5, 36(sp)
+    lw a6, 40(sp)
+    lw a7, 44(sp)
+    lw t3, 48(sp)
+    lw t4, 52(sp)
+    lw t5, 56(sp)
+    lw t6, 60(sp)
+    addi sp, sp, 64
+    mret
+    "#
+);
+
+#[unsafe(no_mangle)]
+extern "C" fn qemu_trap() {
+    let mcause: usize;
+    let mepc: usize;
+    unsafe {
+        asm!("csrr {}, mcause", out(reg) mcause);
+        asm!("csrr {}, mepc", out(reg) mepc);
+    }
+
+    // machine software interrupt
+    if mcause == 0x8000_0003 {
+        unsafe { ptr::write_volatile(CLINT_MSIP, 0) };
+        litmus::on_swi(mepc);
+    } else {
+        exit((mcause as u32) << 16 | 0x3333);
+    }
+}
+
+fn exit(code: u32) -> ! {
+    unsafe { ptr::write_volatile(TEST_FINISHER, code) };
+    loop {
+        continue;
+    }
+}
+
+struct Uart;
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            unsafe {
+                // wait for THR empty
+                while ptr::read_volatile(UART0_LSR) & 0x20 == 0 {}
+                ptr::write_volatile(UART0_THR, b);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Qemu;
+
+impl Platform for Qemu {
+    fn name(&self) -> &'static str {
+        "QEMU virt"
+    }
+
+    fn swi_trigger(&self) -> Option<Trigger> {
+        Some(Trigger {
+            addr: CLINT_MSIP,
+            value: 1,
+        })
+    }
+
+    fn enable_swi(&mut self) {
+        // mie.MSIE
+        unsafe { asm!("csrs mie, {}", in(reg) 0x8usize) };
+    }
+
+    fn disable_swi(&mut self) {
+        unsafe {
+            asm!("csrc mie, {}", in(reg) 0x8usize);
+            ptr::write_volatile(CLINT_MSIP, 0);
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn qemu_main() -> ! {
+    let summary = litmus::run(&mut Qemu, &mut TextReport(Uart));
+    if summary.failed == 0 {
+        exit(0x5555);
+    } else {
+        exit(summary.failed << 16 | 0x3333);
+    }
+}
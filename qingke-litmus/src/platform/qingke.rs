@@ -0,0 +1,64 @@
+//! QingKe chips through qingke-rt, the software interrupt is pended in the PFIC
+use qingke::pfic;
+use qingke_rt::CoreInterrupt;
+
+use crate::litmus::{self, Platform, Trigger};
+use crate::report::{RamLog, TextReport};
+
+/// Interrupt pending set register
+const PFIC_IPSR0: *mut u32 = 0xE000E200 as *mut u32;
+
+const SOFTWARE: u8 = CoreInterrupt::Software as u8;
+
+// There is no PAC in this image, provide an empty peripheral vector table
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".vector_table.external_interrupts")]
+static __EXTERNAL_INTERRUPTS: [Option<unsafe extern "C" fn()>; 0] = [];
+
+struct QingKe;
+
+impl Platform for QingKe {
+    fn name(&self) -> &'static str {
+        if cfg!(feature = "v3a") {
+            "QingKe V3A"
+        } else if cfg!(feature = "v3b") {
+            "QingKe V3B"
+        } else {
+            "QingKe V4"
+        }
+    }
+
+    fn swi_trigger(&self) -> Option<Trigger> {
+        Some(Trigger {
+            addr: PFIC_IPSR0,
+            value: 1 << SOFTWARE,
+        })
+    }
+
+    fn enable_swi(&mut self) {
+        unsafe { pfic::enable_interrupt(SOFTWARE) };
+    }
+
+    fn disable_swi(&mut self) {
+        unsafe {
+            pfic::disable_interrupt(SOFTWARE);
+            pfic::unpend_interrupt(SOFTWARE);
+        }
+    }
+}
+
+#[qingke_rt::interrupt(core)]
+fn Software() {
+    litmus::on_swi(qingke::riscv::register::mepc::read());
+}
+
+#[qingke_rt::entry]
+fn main() -> ! {
+    // Swap in a UART-backed `fmt::Write` here if the board has a console
+    let mut report = TextReport(RamLog::new());
+    litmus::run(&mut QingKe, &mut report);
+
+    loop {
+        unsafe { core::arch::asm!("wfi") };
+    }
+}
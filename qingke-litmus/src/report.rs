@@ -0,0 +1,102 @@
+//! Pluggable result output
+use core::fmt::Write;
+#[cfg(not(feature = "qemu"))]
+use core::{fmt, ptr};
+
+use crate::litmus::{Outcome, Summary};
+
+pub trait Report {
+    fn begin(&mut self, platform: &str, tests: usize);
+    fn result(&mut self, name: &str, outcome: &Outcome);
+    fn end(&mut self, summary: &Summary);
+}
+
+/// Plain text report over any `fmt::Write`, e.g. a UART
+pub struct TextReport<W>(pub W);
+
+impl<W: Write> Report for TextReport<W> {
+    fn begin(&mut self, platform: &str, tests: usize) {
+        let _ = writeln!(self.0, "qingke-litmus on {platform}, {tests} tests");
+    }
+
+    fn result(&mut self, name: &str, outcome: &Outcome) {
+        let _ = match outcome {
+            Outcome::Pass => writeln!(self.0, "PASS {name}"),
+            Outcome::Fail { expected, observed } => writeln!(
+                self.0,
+                "FAIL {name}: expected {expected:#x}, observed {observed:#x}"
+            ),
+            Outcome::Observed(value) => writeln!(self.0, "INFO {name}: {value:#x}"),
+            Outcome::Skipped(reason) => writeln!(self.0, "SKIP {name}: {reason}"),
+        };
+    }
+
+    fn end(&mut self, summary: &Summary) {
+        let _ = writeln!(
+            self.0,
+            "done: {} passed, {} failed, {} observed, {} skipped",
+            summary.passed, summary.failed, summary.observed, summary.skipped
+        );
+    }
+}
+
+#[cfg(not(feature = "qemu"))]
+const RAM_LOG_SIZE: usize = 2048;
+/// "LTMS"
+#[cfg(not(feature = "qemu"))]
+const RAM_LOG_MAGIC: u32 = 0x534d_544c;
+
+/// Header followed by the text log, read back with a debugger from the
+/// `LITMUS_LOG` symbol
+#[cfg(not(feature = "qemu"))]
+#[repr(C)]
+pub struct RamLogBuffer {
+    magic: u32,
+    len: u32,
+    buf: [u8; RAM_LOG_SIZE],
+}
+
+#[cfg(not(feature = "qemu"))]
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".uninit.LITMUS_LOG")]
+static mut LITMUS_LOG: RamLogBuffer = RamLogBuffer {
+    magic: 0,
+    len: 0,
+    buf: [0; RAM_LOG_SIZE],
+};
+
+/// `fmt::Write` into the `LITMUS_LOG` buffer, for boards without a console
+#[cfg(not(feature = "qemu"))]
+pub struct RamLog;
+
+#[cfg(not(feature = "qemu"))]
+impl RamLog {
+    pub fn new() -> Self {
+        let log = &raw mut LITMUS_LOG;
+        unsafe {
+            ptr::write_volatile(&raw mut (*log).len, 0);
+            ptr::write_volatile(&raw mut (*log).magic, RAM_LOG_MAGIC);
+        }
+        RamLog
+    }
+}
+
+#[cfg(not(feature = "qemu"))]
+impl Write for RamLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let log = &raw mut LITMUS_LOG;
+        unsafe {
+            let start = ptr::read_volatile(&raw const (*log).len) as usize;
+            // keep what fits, the log then ends where it overflowed
+            let len = s.len().min(RAM_LOG_SIZE - start);
+            for (i, &b) in s.as_bytes()[..len].iter().enumerate() {
+                ptr::write_volatile((&raw mut (*log).buf).cast::<u8>().add(start + i), b);
+            }
+            ptr::write_volatile(&raw mut (*log).len, (start + len) as u32);
+            if len < s.len() {
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1 +0,0 @@
-ASSERT(_highcode_vma_start % 1024 == 0, "vector table must be 1KB aligned for Qingke V2");
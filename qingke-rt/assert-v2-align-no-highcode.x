@@ -1 +0,0 @@
-ASSERT(_start % 1024 == 0, "vector table must be 1KB aligned for Qingke V2");
@@ -0,0 +1,110 @@
+//! Parts with a generated `memory.x`
+use std::env;
+
+/// A part with a generated `memory.x`, sizes in KiB
+struct Chip {
+    /// Feature name
+    name: &'static str,
+    flash: u32,
+    ram: u32,
+    /// Code flash and RAM share 320K, split by the `SRAM_CODE_MODE` option bytes
+    flash_ram_split: bool,
+}
+
+const fn chip(name: &'static str, flash: u32, ram: u32) -> Chip {
+    Chip {
+        name,
+        flash,
+        ram,
+        flash_ram_split: false,
+    }
+}
+
+const fn split_chip(name: &'static str) -> Chip {
+    Chip {
+        name,
+        flash: 256,
+        ram: 64,
+        flash_ram_split: true,
+    }
+}
+
+const CHIPS: &[Chip] = &[
+    chip("ch32v003f4", 16, 2),
+    chip("ch32v003a4", 16, 2),
+    chip("ch32v003j4", 16, 2),
+    chip("ch32v203f6", 32, 10),
+    chip("ch32v203g6", 32, 10),
+    chip("ch32v203k6", 32, 10),
+    chip("ch32v203c6", 32, 10),
+    chip("ch32v203f8", 64, 20),
+    chip("ch32v203g8", 64, 20),
+    chip("ch32v203k8", 64, 20),
+    chip("ch32v203c8", 64, 20),
+    chip("ch32v203rb", 128, 64),
+    chip("ch32v303cb", 128, 32),
+    chip("ch32v303rb", 128, 32),
+    split_chip("ch32v303rc"),
+    split_chip("ch32v303vc"),
+    chip("ch32v305fb", 128, 32),
+    chip("ch32v305rb", 128, 32),
+    split_chip("ch32v307rc"),
+    split_chip("ch32v307wc"),
+    split_chip("ch32v307vc"),
+];
+
+/// Code flash sizes of the `ch32v30x-flash-*` features, the rest of the 320K is RAM
+const FLASH_RAM_SPLITS: &[u32] = &[192, 224, 256, 288];
+
+pub fn has_feature(name: &str) -> bool {
+    env::var(format!(
+        "CARGO_FEATURE_{}",
+        name.to_uppercase().replace('-', "_")
+    ))
+    .is_ok()
+}
+
+/// `memory.x` of the selected part, `None` when no part is selected
+pub fn memory_x() -> Option<String> {
+    let splits: Vec<u32> = FLASH_RAM_SPLITS
+        .iter()
+        .copied()
+        .filter(|flash| has_feature(&format!("ch32v30x-flash-{flash}k")))
+        .collect();
+    let selected: Vec<&Chip> = CHIPS.iter().filter(|c| has_feature(c.name)).collect();
+
+    let chip = match selected[..] {
+        [] if splits.is_empty() => return None,
+        [] => panic!("`ch32v30x-flash-*` features need a part feature, e.g. `ch32v307vc`"),
+        [chip] => chip,
+        _ => {
+            let names: Vec<_> = selected.iter().map(|c| c.name).collect();
+            panic!("Only one part can be selected, got {}", names.join(", "))
+        }
+    };
+
+    let (flash, ram) = match splits[..] {
+        [] => (chip.flash, chip.ram),
+        [flash] if chip.flash_ram_split => (flash, 320 - flash),
+        [_] => panic!("{} has a fixed flash/RAM split", chip.name),
+        _ => panic!("Only one `ch32v30x-flash-*` feature can be selected"),
+    };
+
+    Some(format!(
+        r#"/* Generated by qingke-rt for {name} */
+MEMORY
+{{
+    FLASH : ORIGIN = 0x00000000, LENGTH = {flash}K
+    RAM : ORIGIN = 0x20000000, LENGTH = {ram}K
+}}
+
+REGION_ALIAS("REGION_TEXT", FLASH);
+REGION_ALIAS("REGION_RODATA", FLASH);
+REGION_ALIAS("REGION_DATA", RAM);
+REGION_ALIAS("REGION_BSS", RAM);
+REGION_ALIAS("REGION_HEAP", RAM);
+REGION_ALIAS("REGION_STACK", RAM);
+"#,
+        name = chip.name.to_uppercase(),
+    ))
+}
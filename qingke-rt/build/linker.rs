@@ -0,0 +1,466 @@
+//! `link.x` generation
+//!
+//! The script is assembled from the layout options instead of picking one of a fixed
+//! set of files, so the options compose.
+use std::fmt::Write;
+
+/// Where the vector table and trap handlers live
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VectorTable {
+    /// Right after the `.init` jump, at the start of flash
+    Flash,
+    /// Copied to RAM in `.highcode`, with the `#[highcode]` functions
+    Ram,
+}
+
+/// Where the stack lives, see the `stack-at-ram-start` feature
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stack {
+    /// At the end of RAM, growing down towards the heap
+    RamEnd,
+    /// At the start of RAM, below `.data` and `.bss` (flip-link style)
+    RamStart,
+}
+
+/// Files INCLUDEd by the generated script, found on the linker search path
+#[derive(Default)]
+pub struct Hooks {
+    pub before_text: Option<String>,
+    pub after_text: Option<String>,
+    pub before_data: Option<String>,
+    pub after_data: Option<String>,
+}
+
+pub struct Layout {
+    pub vector_table: VectorTable,
+    pub stack: Stack,
+    /// Required alignment of the vector table, 1024 on V2
+    pub vector_alignment: u32,
+    /// Offset of the image from the start of `FLASH`, behind a bootloader. A non-zero
+    /// offset puts the `.app_header` there, followed by `_start`
+    pub app_offset: u32,
+    /// Maximum size of the image, e.g. a bootloader that must end before the application
+    pub app_size: Option<u32>,
+    /// Extra `MEMORY` regions with their own `.data.<name>` and `.bss.<name>`
+    pub extra_ram: Vec<String>,
+    pub hooks: Hooks,
+    /// The `alloc` feature, with its `AllocError` hook
+    pub alloc: bool,
+    /// The `w-xor-x` feature, whose PMP entries need the layout checked
+    pub w_xor_x: bool,
+}
+
+const PRELUDE: &str = r#"INCLUDE memory.x
+/* Provides weak aliases (cf. PROVIDED) for device specific interrupt handlers */
+/* This will usually be provided by a device crate generated using svd2rust (see `device.x`) */
+INCLUDE device.x
+
+PROVIDE(_stext = ORIGIN(REGION_TEXT));
+/* Memory layout, override any of these in memory.x, e.g. `_heap_size = 4K;` */
+/* initial stack pointer, used when the stack is at the end of RAM */
+PROVIDE(_stack_start = ORIGIN(RAM) + LENGTH(RAM));
+PROVIDE(_max_hart_id = 0);
+/* stack space kept free of statics and heap, a quarter of the smallest RAMs */
+PROVIDE(_hart_stack_size = MIN(2K, LENGTH(RAM) / 4));
+PROVIDE(_heap_size = 0);
+
+/* fault handlers */
+PROVIDE(InstructionMisaligned = ExceptionHandler);
+PROVIDE(InstructionFault = ExceptionHandler);
+PROVIDE(IllegalInstruction = ExceptionHandler);
+PROVIDE(Breakpoint = ExceptionHandler);
+PROVIDE(LoadMisaligned = ExceptionHandler);
+PROVIDE(LoadFault = ExceptionHandler);
+PROVIDE(StoreMisaligned = ExceptionHandler);
+PROVIDE(StoreFault = ExceptionHandler);
+PROVIDE(UserEnvCall = ExceptionHandler);
+PROVIDE(MachineEnvCall = ExceptionHandler);
+
+/* core interrupt handlers */
+PROVIDE(NonMaskableInt = DefaultHandler);
+PROVIDE(SysTick = DefaultHandler);
+PROVIDE(Software = DefaultHandler);
+
+PROVIDE(Exception = _exception_handler);
+
+PROVIDE(DefaultHandler = DefaultInterruptHandler);
+PROVIDE(__pre_init = DefaultPreInit);
+PROVIDE(StackOverflow = DefaultStackOverflow);
+PROVIDE(ExceptionHandler = DefaultExceptionHandler);
+
+/* # Interrupt vectors */
+EXTERN(__CORE_INTERRUPTS);
+EXTERN(__EXTERNAL_INTERRUPTS); /* `static` variable similar to `__EXCEPTIONS` */
+
+ENTRY(_start)
+"#;
+
+const VECTOR_TABLE_FLASH: &str = r#"
+    .vector_table {start} :
+    {
+        KEEP(*(SORT_NONE(.init)))
+        . = ALIGN(4);
+        /* core interrupts table's first two entries are omitted, occupied by the init jump */
+        KEEP(*(.vector_table.core_interrupts));
+        KEEP(*(.vector_table.external_interrupts));
+        KEEP(*(.vector_table.exceptions));
+        *(.trap .trap.rust)
+    } >FLASH AT>FLASH
+"#;
+
+const VECTOR_TABLE_RAM: &str = r#"
+    /* init jump opcode */
+    .init {start} :
+    {
+        . = ALIGN(4);
+        KEEP(*(SORT_NONE(.init)))
+        . = ALIGN(4);
+    } >FLASH AT>FLASH
+
+    /* highcode section will be copied to RAM, starting with the vector table */
+    .highcode : ALIGN({vector_alignment})
+    {
+        _highcode_lma = LOADADDR(.highcode);
+        PROVIDE(_highcode_vma_start = .);
+        LONG(_start); LONG(0); /* Placeholders for the first two vectors */
+        KEEP(*(.vector_table.core_interrupts));
+        KEEP(*(.vector_table.external_interrupts));
+        KEEP(*(.vector_table.exceptions));
+        *(.trap .trap.rust)
+        *(.highcode);
+        *(.highcode.*);
+        . = ALIGN(4);
+        PROVIDE(_highcode_vma_end = .);
+    } >RAM AT>FLASH
+"#;
+
+const APP_HEADER: &str = r#"
+    /* image header read by `qingke_rt::boot::jump_to`, `_start` follows */
+    .app_header ORIGIN(FLASH) + {app_offset} :
+    {
+        LONG(0x50414b51); /* "QKAP" */
+        LONG(_start);
+        LONG(_stack_top);
+        LONG(__global_pointer$);
+    } >FLASH AT>FLASH
+"#;
+
+const STACK_RAM_START: &str = r#"
+    /* stack at the start of RAM, an overflow runs off RAM instead of into statics */
+    .stack ORIGIN(RAM) (NOLOAD) :
+    {
+        PROVIDE(_stack_bottom = . );
+        . += _hart_stack_size;
+        . = ALIGN(4);
+        PROVIDE(_stack_top = . );
+    } >RAM
+"#;
+
+const STACK_RAM_START_CHECKS: &str = r#"
+ASSERT(_hart_stack_size > 0 && _hart_stack_size % 4 == 0,
+    "_hart_stack_size must be a non-zero multiple of 4 to reserve the stack");
+ASSERT(_stack_top - _stack_bottom == _hart_stack_size,
+    "the stack must keep _hart_stack_size bytes reserved at the start of RAM");
+"#;
+
+const STACK_RAM_END: &str = r#"
+    /* stack at the end of RAM, growing down towards the heap */
+    .stack _stack_start (NOLOAD) :
+    {
+        . = ALIGN(4);
+        PROVIDE(_stack_top = . );
+    } >RAM
+    PROVIDE(_stack_bottom = _eheap);
+"#;
+
+const STATICS: &str = r#"
+    .data : ALIGN(4)
+    {
+        _data_lma = LOADADDR(.data);
+        PROVIDE(_data_vma = .);
+        PROVIDE( __global_pointer$ = . + 0x800 );
+        *(.sdata .sdata.* .sdata2 .sdata2.*);
+        *(.data .data.*);
+        . = ALIGN(4);
+        PROVIDE( _edata = .);
+    } >RAM AT>FLASH
+
+    .bss (NOLOAD) : ALIGN(4)
+    {
+        PROVIDE( _sbss = .);
+        *(.sbss .sbss.* .bss .bss.*);
+        PROVIDE( _ebss = .);
+    } >RAM
+
+    .uninit (NOLOAD) : ALIGN(4)
+    {
+        *(.uninit .uninit.*);
+        . = ALIGN(4);
+        PROVIDE( _euninit = .);
+    } >RAM
+
+    /* heap for an allocator, between _sheap and _eheap */
+    .heap (NOLOAD) : ALIGN(4)
+    {
+        PROVIDE( _sheap = .);
+        . += _heap_size;
+        . = ALIGN(4);
+        PROVIDE( _eheap = .);
+    } >RAM
+"#;
+
+const DISCARDED: &str = r#"
+    .got (INFO) :
+    {
+        KEEP(*(.got .got.*));
+    }
+
+    .eh_frame (INFO) : { KEEP(*(.eh_frame)) }
+    .eh_frame_hdr (INFO) : { *(.eh_frame_hdr) }
+"#;
+
+const CHECKS: &str = r#"
+/* .data + .bss + .uninit + heap + stack must fit in RAM */
+ASSERT(_heap_size % 4 == 0, "_heap_size must be a multiple of 4");
+ASSERT(_stack_bottom + _hart_stack_size <= _stack_top,
+    "not enough RAM: statics + _heap_size + _hart_stack_size exceed the RAM region");
+
+/* no-access region at the bottom of the stack, for the `stack-guard` feature */
+PROVIDE(_stack_guard_size = 32);
+_stack_guard_start = ALIGN(_stack_bottom, _stack_guard_size);
+_stack_guard_end = _stack_guard_start + _stack_guard_size;
+ASSERT(_stack_guard_size >= 8 && (_stack_guard_size & (_stack_guard_size - 1)) == 0,
+    "_stack_guard_size must be a power of 2, at least 8");
+"#;
+
+const W_XOR_X_NAPOT_CHECKS: &str = r#"
+/* w-xor-x NAPOT entries: FLASH and RAM rounded up to a power of 2 must stay aligned */
+ASSERT(__flash_start % (1 << LOG2CEIL(__flash_end - __flash_start)) == 0,
+    "w-xor-x: the FLASH origin must be aligned to its length rounded up to a power of 2");
+ASSERT(__ram_start % (1 << LOG2CEIL(__ram_end - __ram_start)) == 0,
+    "w-xor-x: the RAM origin must be aligned to its length rounded up to a power of 2");
+"#;
+
+const W_XOR_X_TOR_CHECKS: &str = r#"
+/* w-xor-x TOR entries: RAM is executable from its start to the end of .highcode */
+ASSERT(_highcode_vma_start == __ram_start, "w-xor-x: .highcode must start at the start of RAM");
+"#;
+
+impl Layout {
+    pub fn link_x(&self) -> String {
+        let mut s = String::from(PRELUDE);
+        if self.alloc {
+            s.push_str("PROVIDE(AllocError = DefaultAllocError);\n");
+        }
+        s.push_str("\nSECTIONS\n{");
+        if self.stack == Stack::RamStart {
+            s.push_str(STACK_RAM_START);
+        }
+
+        let vector_table = match self.vector_table {
+            VectorTable::Flash => VECTOR_TABLE_FLASH,
+            VectorTable::Ram => VECTOR_TABLE_RAM,
+        };
+        let start = if self.app_offset == 0 {
+            // the core resets to the start of flash
+            "ORIGIN(FLASH)".to_string()
+        } else {
+            write!(
+                s,
+                "{}",
+                APP_HEADER.replace("{app_offset}", &format!("{:#x}", self.app_offset))
+            )
+            .unwrap();
+            let alignment = match self.vector_table {
+                VectorTable::Flash => self.vector_alignment,
+                VectorTable::Ram => 4,
+            };
+            format!("ALIGN(ADDR(.app_header) + SIZEOF(.app_header), {alignment})")
+        };
+        s.push_str(
+            &vector_table
+                .replace("{start}", &start)
+                .replace("{vector_alignment}", &self.vector_alignment.to_string()),
+        );
+
+        include_hook(&mut s, &self.hooks.before_text);
+        s.push_str(
+            r#"
+    .text : ALIGN(4)
+    {
+        . = ALIGN(4);
+        KEEP(*(SORT_NONE(.handle_reset)))
+        *(.init.rust)
+        *(.text .text.*)
+"#,
+        );
+        if self.vector_table == VectorTable::Flash {
+            s.push_str("        *(.highcode .highcode.*);\n");
+        }
+        s.push_str(
+            r#"    } >FLASH AT>FLASH
+
+    .rodata : ALIGN(4)
+    {
+        *(.srodata .srodata.*);
+        *(.rodata .rodata.*);
+        . = ALIGN(4);
+        /* handlers registered with #[syscall] */
+        __ssyscalls = .;
+        KEEP(*(.syscalls .syscalls.*));
+        __esyscalls = .;
+    } >FLASH AT>FLASH
+"#,
+        );
+        include_hook(&mut s, &self.hooks.after_text);
+
+        self.tables(&mut s);
+
+        include_hook(&mut s, &self.hooks.before_data);
+        // before .data and .bss, so their `.data.*`/`.bss.*` patterns do not catch these
+        for region in &self.extra_ram {
+            let name = region.to_lowercase();
+            write!(
+                s,
+                r#"
+    .data.{name} : ALIGN(4)
+    {{
+        *(.data.{name} .data.{name}.*);
+        . = ALIGN(4);
+    }} >{region} AT>FLASH
+
+    .bss.{name} (NOLOAD) : ALIGN(4)
+    {{
+        *(.bss.{name} .bss.{name}.*);
+        . = ALIGN(4);
+    }} >{region}
+"#
+            )
+            .unwrap();
+        }
+        s.push_str(STATICS);
+        include_hook(&mut s, &self.hooks.after_data);
+
+        if self.stack == Stack::RamEnd {
+            s.push_str(STACK_RAM_END);
+        }
+        s.push_str(DISCARDED);
+        s.push_str("}\n");
+
+        s.push_str(CHECKS);
+        if self.stack == Stack::RamStart {
+            s.push_str(STACK_RAM_START_CHECKS);
+        }
+        write!(
+            s,
+            r#"
+/* application image, for bootloaders: from the header or _start to the end of the .data load image */
+__app_start = ORIGIN(FLASH) + {offset:#x};
+__app_end = LOADADDR(.data) + SIZEOF(.data);
+__app_size = __app_end - __app_start;
+
+/* FLASH and RAM regions, for the `w-xor-x` feature */
+__flash_start = ORIGIN(FLASH);
+__flash_end = ORIGIN(FLASH) + LENGTH(FLASH);
+__ram_start = ORIGIN(RAM);
+__ram_end = ORIGIN(RAM) + LENGTH(RAM);
+"#,
+            offset = self.app_offset,
+        )
+        .unwrap();
+        if let Some(size) = self.app_size {
+            writeln!(
+                s,
+                "ASSERT(__app_size <= {size:#x}, \"the image is larger than QINGKE_RT_APP_SIZE\");"
+            )
+            .unwrap();
+        }
+
+        let vector_table = match self.vector_table {
+            VectorTable::Flash => "_start",
+            VectorTable::Ram => "_highcode_vma_start",
+        };
+        write!(
+            s,
+            r#"
+/* the reset jump at _start is auipc + jr, it reaches .handle_reset wherever it is placed */
+ASSERT(__CORE_INTERRUPTS == {vector_table} + 8,
+    "the reset jump at _start must take exactly the first two vector table entries");
+"#
+        )
+        .unwrap();
+        if self.vector_alignment > 4 {
+            write!(
+                s,
+                "\nASSERT({vector_table} % {align} == 0, \"vector table must be {align} bytes aligned for this QingKe core\");\n",
+                align = self.vector_alignment,
+            )
+            .unwrap();
+        }
+        if self.w_xor_x {
+            s.push_str(match self.vector_table {
+                VectorTable::Flash => W_XOR_X_NAPOT_CHECKS,
+                VectorTable::Ram => W_XOR_X_TOR_CHECKS,
+            });
+        }
+        s
+    }
+
+    /// `.copy_table` and `.zero_table`, walked by the reset code
+    fn tables(&self, s: &mut String) {
+        s.push_str(
+            r#"
+    /* (load address, start, end) of each section copied to RAM at reset */
+    .copy_table : ALIGN(4)
+    {
+        __scopy_table = .;
+"#,
+        );
+        if self.vector_table == VectorTable::Ram {
+            s.push_str(
+                "        LONG(_highcode_lma); LONG(_highcode_vma_start); LONG(_highcode_vma_end);\n",
+            );
+        }
+        s.push_str("        LONG(_data_lma); LONG(_data_vma); LONG(_edata);\n");
+        for region in &self.extra_ram {
+            let section = format!(".data.{}", region.to_lowercase());
+            writeln!(
+                s,
+                "        LONG(LOADADDR({section})); LONG(ADDR({section})); LONG(ADDR({section}) + SIZEOF({section}));"
+            )
+            .unwrap();
+        }
+        s.push_str(
+            r#"    } >FLASH AT>FLASH
+    /* memory.x adds entries with `SECTIONS { ... } INSERT AFTER .copy_table;` */
+    __ecopy_table = .;
+
+    /* (start, end) of each section zeroed at reset */
+    .zero_table : ALIGN(4)
+    {
+        __szero_table = .;
+        LONG(_sbss); LONG(_ebss);
+"#,
+        );
+        for region in &self.extra_ram {
+            let section = format!(".bss.{}", region.to_lowercase());
+            writeln!(
+                s,
+                "        LONG(ADDR({section})); LONG(ADDR({section}) + SIZEOF({section}));"
+            )
+            .unwrap();
+        }
+        s.push_str(
+            r#"    } >FLASH AT>FLASH
+    /* memory.x adds entries with `SECTIONS { ... } INSERT AFTER .zero_table;` */
+    __ezero_table = .;
+"#,
+        );
+    }
+}
+
+fn include_hook(s: &mut String, hook: &Option<String>) {
+    if let Some(file) = hook {
+        writeln!(s, "\n    INCLUDE {file}").unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::{env, fs};
+
+mod chips;
+mod linker;
+
+use chips::has_feature;
+use linker::{Hooks, Layout, Stack, VectorTable};
+
+/// Parse the target RISC-V architecture and returns its bit width and the extension set
+fn parse_target(target: &str, cargo_flags: &str) -> (u32, HashSet<char>) {
+    // isolate bit width and extensions from the rest of the target information
+    let arch = target
+        .trim_start_matches("riscv")
+        .split('-')
+        .next()
+        .unwrap();
+
+    let bits = arch
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse::<u32>()
+        .unwrap();
+
+    let mut extensions: HashSet<char> = arch.chars().skip_while(|c| c.is_ascii_digit()).collect();
+    // expand the 'g' shorthand extension
+    if extensions.contains(&'g') {
+        extensions.insert('i');
+        extensions.insert('m');
+        extensions.insert('a');
+        extensions.insert('f');
+        extensions.insert('d');
+    }
+
+    let cargo_flags = cargo_flags
+        .split(0x1fu8 as char)
+        .filter(|arg| !arg.is_empty());
+
+    cargo_flags
+        .filter(|k| k.starts_with("target-feature="))
+        .flat_map(|str| {
+            let flags = str.split('=').collect::<Vec<&str>>()[1];
+            flags.split(',')
+        })
+        .for_each(|feature| {
+            let chars = feature.chars().collect::<Vec<char>>();
+            match chars[0] {
+                '+' => {
+                    extensions.insert(chars[1]);
+                }
+                '-' => {
+                    extensions.remove(&chars[1]);
+                }
+                _ => {
+                    panic!("Unsupported target feature operation");
+                }
+            }
+        });
+
+    (bits, extensions)
+}
+
+/// Parse a size or address: decimal, `0x` hex, with an optional `K` suffix
+fn parse_size(name: &str, value: &str) -> u32 {
+    let value = value.trim();
+    let (digits, scale) = match value.strip_suffix(['K', 'k']) {
+        Some(digits) => (digits, 1024),
+        None => (value, 1),
+    };
+    let number = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    match number {
+        Ok(number) => number * scale,
+        Err(_) => panic!("{name}: expected a size like 8K, 0x2000 or 8192, got `{value}`"),
+    }
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // Searched after the application's own link paths, so its memory.x wins
+    if let Some(memory_x) = chips::memory_x() {
+        fs::write(out_dir.join("memory.x"), memory_x).unwrap();
+    }
+
+    // Core capabilities from qingke's core model
+    let capability = |key: &str| env::var(format!("DEP_QINGKE_{key}")).unwrap();
+    let vector_alignment = capability("VECTOR_ALIGNMENT");
+
+    // Layout options, from features and `QINGKE_RT_*` variables (e.g. in `[env]` of .cargo/config.toml)
+    let config = |key: &str| {
+        println!("cargo:rerun-if-env-changed=QINGKE_RT_{key}");
+        env::var(format!("QINGKE_RT_{key}"))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    let app_offset = config("APP_OFFSET").map_or(0, |v| parse_size("QINGKE_RT_APP_OFFSET", &v));
+    let app_size = config("APP_SIZE").map(|v| parse_size("QINGKE_RT_APP_SIZE", &v));
+    if app_offset % 4 != 0 {
+        panic!("QINGKE_RT_APP_OFFSET must be 4 bytes aligned");
+    }
+
+    let layout = Layout {
+        vector_table: if has_feature("highcode") {
+            VectorTable::Ram
+        } else {
+            VectorTable::Flash
+        },
+        stack: if has_feature("stack-at-ram-start") {
+            Stack::RamStart
+        } else {
+            Stack::RamEnd
+        },
+        vector_alignment: vector_alignment.parse().unwrap(),
+        app_offset,
+        app_size,
+        extra_ram: config("EXTRA_RAM")
+            .map(|regions| regions.split(',').map(|r| r.trim().to_string()).collect())
+            .unwrap_or_default(),
+        hooks: Hooks {
+            before_text: config("BEFORE_TEXT"),
+            after_text: config("AFTER_TEXT"),
+            before_data: config("BEFORE_DATA"),
+            after_data: config("AFTER_DATA"),
+        },
+        alloc: has_feature("alloc"),
+        w_xor_x: has_feature("w-xor-x"),
+    };
+    fs::write(out_dir.join("link.x"), layout.link_x()).unwrap();
+
+    println!("cargo:rustc-link-search={}", out_dir.display());
+
+    println!("cargo:rerun-if-changed=build");
+
+    let target = env::var("TARGET").unwrap();
+    let cargo_flags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap();
+    // set configuration flags depending on the target
+
+    println!("cargo::rustc-check-cfg=cfg(riscvf)");
+    println!("cargo::rustc-check-cfg=cfg(riscvd)");
+
+    // re-emit the core capabilities as the same cfgs as in qingke
+    println!("cargo::rustc-check-cfg=cfg(qingke_core, values(\"v2\", \"v3a\", \"v3b\", \"v4\"))");
+    println!("cargo::rustc-check-cfg=cfg(has_gintenr)");
+    println!("cargo::rustc-check-cfg=cfg(has_vtf_id_register)");
+    println!("cargo::rustc-check-cfg=cfg(hpe_depth, values(\"0\", \"2\", \"3\"))");
+    println!("cargo::rustc-check-cfg=cfg(has_umode)");
+    println!("cargo::rustc-check-cfg=cfg(vector_alignment, values(\"4\", \"1024\"))");
+    println!("cargo::rustc-check-cfg=cfg(has_vectored_address)");
+    println!("cargo::rustc-check-cfg=cfg(pmp_regions, values(\"0\", \"4\"))");
+    println!("cargo::rustc-check-cfg=cfg(pmp_granularity, values(\"0\", \"4\"))");
+
+    println!("cargo:rustc-cfg=qingke_core=\"{}\"", capability("CORE"));
+    println!("cargo:rustc-cfg=hpe_depth=\"{}\"", capability("HPE_DEPTH"));
+    println!("cargo:rustc-cfg=vector_alignment=\"{vector_alignment}\"");
+    println!(
+        "cargo:rustc-cfg=pmp_regions=\"{}\"",
+        capability("PMP_REGIONS")
+    );
+    println!(
+        "cargo:rustc-cfg=pmp_granularity=\"{}\"",
+        capability("PMP_GRANULARITY")
+    );
+    for cfg in [
+        "has_gintenr",
+        "has_vtf_id_register",
+        "has_umode",
+        "has_vectored_address",
+    ] {
+        if capability(&cfg.to_uppercase()) == "true" {
+            println!("cargo:rustc-cfg={cfg}");
+        }
+    }
+
+    if target.starts_with("riscv") {
+        println!("cargo:rustc-cfg=riscv");
+
+        // This is required until target_arch & target_feature risc-v work is
+        // stable and in-use (rust 1.75.0)
+        let (_bits, extensions) = parse_target(&target, &cargo_flags);
+
+        // expose the ISA extensions
+        for ext in &extensions {
+            println!("cargo:rustc-cfg=riscv{}", ext);
+        }
+    }
+}
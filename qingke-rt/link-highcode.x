@@ -1,122 +0,0 @@
-INCLUDE memory.x
-/* Provides weak aliases (cf. PROVIDED) for device specific interrupt handlers */
-/* This will usually be provided by a device crate generated using svd2rust (see `device.x`) */
-INCLUDE device.x
-
-PROVIDE(_stext = ORIGIN(REGION_TEXT));
-PROVIDE(_stack_start = ORIGIN(REGION_STACK) + LENGTH(REGION_STACK));
-PROVIDE(_max_hart_id = 0);
-PROVIDE(_hart_stack_size = 2K);
-PROVIDE(_heap_size = 0);
-
-/* fault handlers */
-PROVIDE(InstructionMisaligned = ExceptionHandler);
-PROVIDE(InstructionFault = ExceptionHandler);
-PROVIDE(IllegalInstruction = ExceptionHandler);
-PROVIDE(Breakpoint = ExceptionHandler);
-PROVIDE(LoadMisaligned = ExceptionHandler);
-PROVIDE(LoadFault = ExceptionHandler);
-PROVIDE(StoreMisaligned = ExceptionHandler);
-PROVIDE(StoreFault = ExceptionHandler);;
-PROVIDE(UserEnvCall = ExceptionHandler);
-PROVIDE(MachineEnvCall = ExceptionHandler);
-
-/* core interrupt handlers */
-PROVIDE(NonMaskableInt = DefaultHandler);
-PROVIDE(SysTick = DefaultHandler);
-PROVIDE(Software = DefaultHandler);
-
-PROVIDE(Exception = _exception_handler);
-
-PROVIDE(DefaultHandler = DefaultInterruptHandler);
-PROVIDE(ExceptionHandler = DefaultExceptionHandler);
-
-/* PROVIDE(__EXTERNAL_INTERRUPTS = __DEFAULT_EXTERNAL_INTERRUPTS);*/
-
-/* # Interrupt vectors */
-EXTERN(__CORE_INTERRUPTS);
-EXTERN(__EXTERNAL_INTERRUPTS); /* `static` variable similar to `__EXCEPTIONS` */
-
-ENTRY(_start)
-
-SECTIONS
-{
-    /* init jump opcode */
-    .init :
-    {
-        . = ALIGN(4);
-        KEEP(*(SORT_NONE(.init)))
-        . = ALIGN(4);
-    } >FLASH AT>FLASH
-
-    /* highcode section will be copied to RAM */
-    .highcode : ALIGN(4)
-    {
-        _highcode_lma = LOADADDR(.highcode);
-        PROVIDE(_highcode_vma_start = .);
-        LONG(_start); /* Placeholder for the first vector */
-        KEEP(*(.vector_table.core_interrupts));
-        KEEP(*(.vector_table.external_interrupts));
-        KEEP(*(.vector_table.exceptions));
-        *(.trap .trap.rust)
-        *(.highcode);
-        *(.highcode.*);
-		. = ALIGN(4);
-        PROVIDE(_highcode_vma_end = .);
-    } >RAM AT>FLASH
-
-    /* FIXME: if highcode section is large enough, then .init jump might be impossible to jump to .handle_reset */
-    .text : ALIGN(4)
-    {
-        . = ALIGN(4);
-        KEEP(*(SORT_NONE(.handle_reset)))
-        *(.init.rust)
-        *(.text .text.*)
-    } >FLASH AT>FLASH
-
-    .rodata : ALIGN(4)
-    {
-        *(.srodata .srodata.*);
-        *(.rodata .rodata.*);
-        . = ALIGN(4);
-    } >FLASH AT>FLASH
-
-    .data : ALIGN(4)
-    {
-        _data_lma = LOADADDR(.data);
-        PROVIDE(_data_vma = .);
-        PROVIDE( __global_pointer$ = . + 0x800 );
-        *(.sdata .sdata.* .sdata2 .sdata2.*);
-        *(.data .data.*);
-        . = ALIGN(4);
-        PROVIDE( _edata = .);
-    } >RAM AT>FLASH
-
-    .bss (NOLOAD) : ALIGN(4)
-    {
-        PROVIDE( _sbss = .);
-        *(.sbss .sbss.* .bss .bss.*);
-        PROVIDE( _ebss = .);
-    } >RAM
-
-    .uninit (NOLOAD) : ALIGN(4)
-    {
-        *(.uninit .uninit.*);
-    } >RAM
-
-    .stack ORIGIN(RAM)+LENGTH(RAM) (NOLOAD) :
-    {
-        . = ALIGN(4);
-        PROVIDE(_stack_top = . );
-    } >RAM
-
-    .got (INFO) :
-    {
-        KEEP(*(.got .got.*));
-    }
-
-    .eh_frame (INFO) : { KEEP(*(.eh_frame)) }
-    .eh_frame_hdr (INFO) : { *(.eh_frame_hdr) }
-}
-
-INCLUDE assert-align.x
@@ -1,10 +1,10 @@
 use std::iter;
 
-use proc_macro2::Span;
 use proc_macro_error::proc_macro_error;
+use proc_macro2::Span;
 use syn::{
-    parse, parse_macro_input, spanned::Spanned, Ident, ItemFn, PathArguments, ReturnType, Type,
-    Visibility,
+    Ident, ItemFn, PathArguments, ReturnType, Type, Visibility, parse, parse_macro_input,
+    spanned::Spanned,
 };
 
 use proc_macro::TokenStream;
@@ -69,14 +69,283 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
     .into()
 }
 
-#[allow(unused)]
-fn is_simple_type(ty: &Type, name: &str) -> bool {
-    if let Type::Path(p) = ty {
-        if p.qself.is_none() && p.path.leading_colon.is_none() && p.path.segments.len() == 1 {
-            let segment = p.path.segments.first().unwrap();
-            if segment.ident == name && segment.arguments == PathArguments::None {
-                return true;
+/// Marks a function to be called at reset, before `.data`, `.bss` and `.highcode` are initialized.
+///
+/// Use it to configure clocks, flash wait states or extra SRAM banks before the startup code
+/// touches them. Only the stack is usable: statics are not initialized yet.
+///
+/// Usage:
+/// ```ignore
+/// #[pre_init]
+/// unsafe fn before_main() { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn pre_init(args: TokenStream, input: TokenStream) -> TokenStream {
+    let f = parse_macro_input!(input as ItemFn);
+
+    // check the function signature
+    let valid_signature = f.sig.constness.is_none()
+        && f.sig.asyncness.is_none()
+        && f.vis == Visibility::Inherited
+        && f.sig.unsafety.is_some()
+        && f.sig.abi.is_none()
+        && f.sig.inputs.is_empty()
+        && f.sig.generics.params.is_empty()
+        && f.sig.generics.where_clause.is_none()
+        && f.sig.variadic.is_none()
+        && match f.sig.output {
+            ReturnType::Default => true,
+            ReturnType::Type(_, ref ty) => match **ty {
+                Type::Tuple(ref tuple) => tuple.elems.is_empty(),
+                _ => false,
+            },
+        };
+
+    if !valid_signature {
+        return parse::Error::new(
+            f.span(),
+            "`#[pre_init]` function must have signature `unsafe fn()`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "This attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let attrs = f.attrs;
+    let ident = f.sig.ident;
+    let block = f.block;
+
+    quote!(
+        #[unsafe(export_name = "__pre_init")]
+        #(#attrs)*
+        pub unsafe fn #ident() #block
+    )
+    .into()
+}
+
+/// Registers a function as the M-mode handler of a syscall, see `qingke_rt::syscall`.
+///
+/// The argument is the syscall number, any `usize` constant expression. The function takes
+/// up to 4 `usize` arguments and returns a `usize`.
+///
+/// Usage:
+/// ```ignore
+/// #[syscall(1)]
+/// fn read_temperature(channel: usize) -> usize { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn syscall(args: TokenStream, input: TokenStream) -> TokenStream {
+    let f = parse_macro_input!(input as ItemFn);
+
+    if args.is_empty() {
+        return parse::Error::new(Span::call_site(), "`#[syscall]` requires a syscall number")
+            .to_compile_error()
+            .into();
+    }
+    let number = parse_macro_input!(args as syn::Expr);
+
+    // check the function signature
+    let valid_signature = f.sig.constness.is_none()
+        && f.sig.asyncness.is_none()
+        && f.sig.abi.is_none()
+        && f.sig.inputs.len() <= 4
+        && f.sig.inputs.iter().all(|input| match input {
+            syn::FnArg::Typed(arg) => is_simple_type(&arg.ty, "usize"),
+            syn::FnArg::Receiver(_) => false,
+        })
+        && f.sig.generics.params.is_empty()
+        && f.sig.generics.where_clause.is_none()
+        && f.sig.variadic.is_none()
+        && match f.sig.output {
+            ReturnType::Default => false,
+            ReturnType::Type(_, ref ty) => is_simple_type(ty, "usize"),
+        };
+
+    if !valid_signature {
+        return parse::Error::new(
+            f.span(),
+            "`#[syscall]` function must have signature `[unsafe] fn(usize, ...) -> usize`, with at most 4 arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let ident = &f.sig.ident;
+    let args = (0..f.sig.inputs.len()).map(|i| quote!(args[#i]));
+    let call = if f.sig.unsafety.is_some() {
+        quote!(unsafe { #ident(#(#args),*) })
+    } else {
+        quote!(#ident(#(#args),*))
+    };
+
+    quote!(
+        #f
+
+        const _: () = {
+            #[allow(unused_variables)]
+            extern "C" fn handler(args: &[usize; 4]) -> usize {
+                #call
             }
+
+            #[used]
+            #[unsafe(link_section = ".syscalls")]
+            static ENTRY: ::qingke_rt::syscall::SyscallEntry = ::qingke_rt::syscall::SyscallEntry {
+                number: #number,
+                handler,
+            };
+        };
+    )
+    .into()
+}
+
+/// Exceptions of the `__EXCEPTIONS` table, and the fallback of the others
+const EXCEPTIONS: [&str; 11] = [
+    "InstructionMisaligned",
+    "InstructionFault",
+    "IllegalInstruction",
+    "Breakpoint",
+    "LoadMisaligned",
+    "LoadFault",
+    "StoreMisaligned",
+    "StoreFault",
+    "UserEnvCall",
+    "MachineEnvCall",
+    "ExceptionHandler",
+];
+
+/// Marks a function as an exception handler, called by the runtime's exception handler.
+///
+/// The name must be one of the `__EXCEPTIONS` entries, or `ExceptionHandler` for all the
+/// exceptions without a handler. It may take the `qingke_rt::TrapFrame` of the faulting code.
+///
+/// Usage:
+/// ```ignore
+/// #[exception]
+/// fn IllegalInstruction(frame: &mut TrapFrame) {
+///     frame.mepc += 4;
+/// }
+///
+/// #[exception]
+/// fn ExceptionHandler() -> ! { ... }
+/// ```
+///
+/// The frame is passed as `&mut`, a shared reference is rejected:
+/// ```compile_fail
+/// # use qingke_rt_macros::exception;
+/// # #[repr(C)] pub struct TrapFrame;
+/// #[exception]
+/// fn LoadFault(frame: &TrapFrame) {}
+/// ```
+#[proc_macro_attribute]
+pub fn exception(args: TokenStream, input: TokenStream) -> TokenStream {
+    let f = parse_macro_input!(input as ItemFn);
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "This attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let name = f.sig.ident.to_string();
+    if !EXCEPTIONS.contains(&name.as_str()) {
+        return parse::Error::new(
+            f.sig.ident.span(),
+            format!(
+                "`{name}` is not an exception, expected one of: {}",
+                EXCEPTIONS.join(", ")
+            ),
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // the runtime dispatches it to the `#[syscall]` handlers, this one would never run
+    if cfg!(feature = "u-mode") && name == "UserEnvCall" {
+        return parse::Error::new(
+            f.sig.ident.span(),
+            "`UserEnvCall` is handled by the runtime with the `u-mode` feature, use `#[syscall]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // check the function signature
+    let valid_signature = f.sig.constness.is_none()
+        && f.sig.asyncness.is_none()
+        && f.vis == Visibility::Inherited
+        && f.sig.abi.is_none()
+        && f.sig.generics.params.is_empty()
+        && f.sig.generics.where_clause.is_none()
+        && f.sig.variadic.is_none()
+        && f.sig.inputs.len() <= 1
+        && f.sig.inputs.iter().all(|input| match input {
+            syn::FnArg::Typed(arg) => match *arg.ty {
+                Type::Reference(ref r) => {
+                    r.lifetime.is_none()
+                        && r.mutability.is_some()
+                        && matches!(*r.elem, Type::Path(ref p) if p.qself.is_none()
+                            && p.path.segments.last().is_some_and(|s| s.ident == "TrapFrame"))
+                }
+                _ => false,
+            },
+            syn::FnArg::Receiver(_) => false,
+        })
+        && match f.sig.output {
+            ReturnType::Default => true,
+            ReturnType::Type(_, ref ty) => match **ty {
+                Type::Tuple(ref tuple) => tuple.elems.is_empty(),
+                Type::Never(..) => true,
+                _ => false,
+            },
+        };
+
+    if !valid_signature {
+        return parse::Error::new(
+            f.span(),
+            "`#[exception]` handlers must have signature `[unsafe] fn([&mut TrapFrame]) [-> !]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let attrs = f.attrs;
+    let unsafety = f.sig.unsafety;
+    let args = f.sig.inputs;
+    let output = f.sig.output;
+    let block = f.block;
+    let ident = Ident::new(&format!("__qingke_rt_{name}"), Span::call_site());
+
+    // called from `_exception_handler_rust`, in `.trap.rust` too
+    quote!(
+        #[allow(non_snake_case)]
+        #[unsafe(export_name = #name)]
+        #[unsafe(link_section = ".trap.rust")]
+        #(#attrs)*
+        pub #unsafety extern "C" fn #ident(#args) #output #block
+    )
+    .into()
+}
+
+/// Floating point registers not preserved across calls: ft0-ft11, fa0-fa7
+const FP_CALLER_SAVED: [&str; 20] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fa0", "fa1", "fa2", "fa3", "fa4",
+    "fa5", "fa6", "fa7", "ft8", "ft9", "ft10", "ft11",
+];
+
+fn is_simple_type(ty: &Type, name: &str) -> bool {
+    if let Type::Path(p) = ty
+        && p.qself.is_none()
+        && p.path.leading_colon.is_none()
+        && p.path.segments.len() == 1
+    {
+        let segment = p.path.segments.first().unwrap();
+        if segment.ident == name && segment.arguments == PathArguments::None {
+            return true;
         }
     }
     false
@@ -102,6 +371,9 @@ pub fn highcode(_args: TokenStream, input: TokenStream) -> TokenStream {
 
 /// Marks a function as an interrupt handler. (Wrapping as a mret function)
 ///
+/// On targets with the F or D extension, the trampoline also saves the caller-saved
+/// floating point registers and `fcsr`, which the hardware prologue does not cover.
+///
 /// Note that Rust has also introduced the `riscv-interrupt-m` and `riscv-interrupt-s` ABI, which
 /// are used for machine and supervisor mode interrupts, respectively. These ABIs can also be used for
 /// Qingke cores, yet they add additional register saving and restoring that is not necessary.
@@ -176,12 +448,9 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
                     is_lowcode_irq = true;
                 }
                 _ => {
-                    return parse::Error::new(
-                        ident.span(),
-                        "expected 'core' or 'lowcode'",
-                    )
-                    .to_compile_error()
-                    .into();
+                    return parse::Error::new(ident.span(), "expected 'core' or 'lowcode'")
+                        .to_compile_error()
+                        .into();
                 }
             }
         }
@@ -234,6 +503,15 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
 
     let wrapped_ident = &f.sig.ident;
 
+    if is_core_irq && EXCEPTIONS.contains(&interrupt.as_str()) {
+        return parse::Error::new(
+            ident.span(),
+            format!("`{interrupt}` is an exception, use `#[exception]`"),
+        )
+        .to_compile_error()
+        .into();
+    }
+
     let stmts = f.block.stmts.clone();
     // check irq names
     if is_core_irq {
@@ -276,26 +554,50 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
         (".trap".to_string(), ".trap.rust".to_string())
     };
 
-    let start_interrupt = format!(
-        r#"
-core::arch::global_asm!(
-    ".section {trampoline_section}, \"ax\"
+    // The hardware prologue/epilogue only covers integer registers: with F or D, the
+    // caller-saved FP registers and fcsr are saved here too.
+    let trampoline = |fp: Option<(&str, &str, usize)>| {
+        let mut save = String::new();
+        let mut restore = String::new();
+        let mut frame = 4;
+        if let Some((store, load, width)) = fp {
+            // fcsr goes through ra, already saved, so no other register is touched
+            save.push_str("frcsr ra\n        sw ra, 4(sp)\n");
+            restore.push_str("lw ra, 4(sp)\n        fscsr ra\n");
+            for (i, reg) in FP_CALLER_SAVED.iter().enumerate() {
+                let offset = 8 + i * width;
+                save.push_str(&format!("        {store} {reg}, {offset}(sp)\n"));
+                restore.push_str(&format!("        {load} {reg}, {offset}(sp)\n"));
+            }
+            frame = (8 + FP_CALLER_SAVED.len() * width).next_multiple_of(16);
+        }
+        format!(
+            ".section {trampoline_section}, \"ax\"
     .align 2
     .global {interrupt}
     {interrupt}:
-        addi sp, sp, -4
+        addi sp, sp, -{frame}
         sw ra, 0(sp)
+        {save}
         jal {wrapped_name}
+        {restore}
         lw ra, 0(sp)
-        addi sp, sp, 4
+        addi sp, sp, {frame}
         mret
-");"#
-    );
-
-    let start_interrupt_asm: proc_macro2::TokenStream = start_interrupt.parse().unwrap();
+"
+        )
+    };
+    let trampoline_int = trampoline(None);
+    let trampoline_f = trampoline(Some(("fsw", "flw", 4)));
+    let trampoline_d = trampoline(Some(("fsd", "fld", 8)));
 
     quote!(
-        #start_interrupt_asm
+        #[cfg(not(target_feature = "f"))]
+        core::arch::global_asm!(#trampoline_int);
+        #[cfg(all(target_feature = "f", not(target_feature = "d")))]
+        core::arch::global_asm!(#trampoline_f);
+        #[cfg(target_feature = "d")]
+        core::arch::global_asm!(#trampoline_d);
 
         #[allow(non_snake_case)]
         #[unsafe(no_mangle)]
@@ -21,9 +21,15 @@ cfg_global_asm! {
     "
     .section    .init,\"ax\"
     .global _start
-    .align  1
+    .align  2
 _start:
-    j handle_reset
+    // auipc + jr: reaches handle_reset anywhere, unlike `j` (±1 MiB). Exactly 8 bytes,
+    // the first two vector table entries (0 and the reserved 1)
+    .option push
+    .option norvc
+    .option norelax
+    tail handle_reset
+    .option pop
     ",
     "
     .section    .handle_reset,\"ax\",@progbits
@@ -36,44 +42,61 @@ handle_reset:
     .option pop
     la sp, _stack_top
     ",
-    // load highcode from flash to ram
-    #[cfg(feature = "highcode")]
+    // user hook, before RAM is initialized
     "
-    la a0, _highcode_lma
-    la a1, _highcode_vma_start
-    la a2, _highcode_vma_end
-    bgeu a1, a2, 2f
+    jal __pre_init
+    ",
+    // copy each (load, start, end) entry of the copy table from flash to RAM,
+    // .highcode and .data by default
+    "
+    la t0, __scopy_table
+    la t1, __ecopy_table
+    bgeu t0, t1, 4f
 1:
-    lw t0, (a0)
-    sw t0, (a1)
+    lw a0, 0(t0)
+    lw a1, 4(t0)
+    lw a2, 8(t0)
+    addi t0, t0, 12
+    bgeu a1, a2, 3f
+2:
+    lw t2, (a0)
+    sw t2, (a1)
     addi a0, a0, 4
     addi a1, a1, 4
-    bltu a1, a2, 1b
-2:
+    bltu a1, a2, 2b
+3:
+    bltu t0, t1, 1b
+4:
     ",
-    // load data from flash to ram
+    // clear each (start, end) entry of the zero table, .bss by default
     "
-    la a0, _data_lma
-    la a1, _data_vma
-    la a2, _edata
-    bgeu a1, a2, 2f
+    la t0, __szero_table
+    la t1, __ezero_table
+    bgeu t0, t1, 4f
 1:
-    lw t0, (a0)
-    sw t0, (a1)
-    addi a0, a0, 4
-    addi a1, a1, 4
-    bltu a1, a2, 1b
+    lw a0, 0(t0)
+    lw a1, 4(t0)
+    addi t0, t0, 8
+    bgeu a0, a1, 3f
 2:
+    sw zero, (a0)
+    addi a0, a0, 4
+    bltu a0, a1, 2b
+3:
+    bltu t0, t1, 1b
+4:
     ",
-    // clear bss section
+
+    // fill the stack up to the stack pointer with the paint pattern
+    #[cfg(feature = "stack-paint")]
     "
-    la a0, _sbss
-    la a1, _ebss
-    bgeu a0, a1, 2f
+    la a0, _stack_bottom
+    li t0, 0xcccccccc
+    bgeu a0, sp, 2f
 1:
-    sw zero, (a0)
+    sw t0, (a0)
     addi a0, a0, 4
-    bltu a0, a1, 1b
+    bltu a0, sp, 1b
 2:
     ",
 
@@ -97,3 +120,80 @@ handle_reset:
     mret
     ",
 }
+
+// Exceptions: the trapping code's registers and trap CSRs are saved as a `TrapFrame`
+// passed to `_exception_handler_rust`, then `mepc` and the registers are reloaded from
+// it. With the hardware prologue/epilogue, `mret` restores the caller-saved registers
+// from the hardware stack over the reloaded ones.
+cfg_global_asm! {
+    "
+    .section .trap, \"ax\"
+    .global _exception_handler
+_exception_handler:
+    ",
+    // With the stack below the guard end, the fault comes from the guard and the stack
+    // is unusable: restart it at the top and report the overflow, never returning.
+    #[cfg(feature = "stack-guard")]
+    "
+    csrw mscratch, t0
+    la t0, _stack_guard_end
+    bgeu sp, t0, 1f
+    csrr a0, mepc
+    la sp, _stack_top
+    la t0, StackOverflow
+    jr t0
+1:
+    csrr t0, mscratch
+    ",
+    "
+    addi sp, sp, -80
+    sw ra, 0(sp)
+    sw t0, 4(sp)
+    sw t1, 8(sp)
+    sw t2, 12(sp)
+    sw a0, 16(sp)
+    sw a1, 20(sp)
+    sw a2, 24(sp)
+    sw a3, 28(sp)
+    sw a4, 32(sp)
+    sw a5, 36(sp)
+    sw a6, 40(sp)
+    sw a7, 44(sp)
+    sw t3, 48(sp)
+    sw t4, 52(sp)
+    sw t5, 56(sp)
+    sw t6, 60(sp)
+    csrr t0, mcause
+    sw t0, 64(sp)
+    csrr t0, mepc
+    sw t0, 68(sp)
+    csrr t0, mtval
+    sw t0, 72(sp)
+    csrr t0, mstatus
+    sw t0, 76(sp)
+
+    mv a0, sp
+    jal _exception_handler_rust
+
+    lw t0, 68(sp)
+    csrw mepc, t0
+    lw ra, 0(sp)
+    lw t0, 4(sp)
+    lw t1, 8(sp)
+    lw t2, 12(sp)
+    lw a0, 16(sp)
+    lw a1, 20(sp)
+    lw a2, 24(sp)
+    lw a3, 28(sp)
+    lw a4, 32(sp)
+    lw a5, 36(sp)
+    lw a6, 40(sp)
+    lw a7, 44(sp)
+    lw t3, 48(sp)
+    lw t4, 52(sp)
+    lw t5, 56(sp)
+    lw t6, 60(sp)
+    addi sp, sp, 80
+    mret
+    ",
+}
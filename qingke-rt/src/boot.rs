@@ -0,0 +1,100 @@
+//! Handoff from a bootloader to another image
+//!
+//! Images built with `QINGKE_RT_APP_OFFSET` start with an [`ImageHeader`], followed by
+//! `_start`. [`jump_to`] puts the core back in its reset state as far as the image's
+//! startup code relies on it, then enters the image.
+use core::arch::asm;
+use core::ptr;
+
+use qingke::pfic;
+use qingke::register::mtvec::{self, TrapMode};
+
+/// `"QKAP"`, first word of the image header
+pub const IMAGE_MAGIC: u32 = 0x5041_4b51;
+
+/// SysTick control and status registers
+const STK_CTLR: *mut u32 = 0xE000_F000 as *mut u32;
+#[cfg(not(qingke_core = "v3a"))]
+const STK_SR: *mut u32 = 0xE000_F004 as *mut u32;
+
+/// Header at the base of an image, written by the linker script
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub magic: u32,
+    /// `_start`
+    pub entry: u32,
+    /// `_stack_top`
+    pub stack_top: u32,
+    /// `__global_pointer$`
+    pub global_pointer: u32,
+}
+
+impl ImageHeader {
+    /// Read the header at `image_base`, `None` if the magic does not match
+    ///
+    /// # Safety
+    ///
+    /// `image_base` must be readable, 4 bytes aligned memory.
+    pub unsafe fn read(image_base: usize) -> Option<Self> {
+        let header = unsafe { ptr::read_volatile(image_base as *const ImageHeader) };
+        (header.magic == IMAGE_MAGIC).then_some(header)
+    }
+}
+
+/// Start the image at `image_base`, never returns
+///
+/// Interrupts are disabled globally and in the PFIC, pending interrupts and the VTF
+/// channels are cleared, SysTick is stopped, `INTSYSCR`, `GINTENR` and `mtvec` get
+/// their reset values. Then `sp` and `gp` are loaded from the header and the image's
+/// `_start` is called.
+///
+/// Panics if there is no valid header at `image_base`, before touching any state.
+///
+/// Locked PMP entries can not be cleared before a reset: the ones set by `stack-guard`
+/// or `w-xor-x` in the calling image stay in force in the started image, whose memory
+/// layout they were not derived from.
+///
+/// # Safety
+///
+/// Must be called in machine mode. Nothing of the calling image may run afterwards:
+/// peripherals it configured, including DMA, keep running unless stopped beforehand.
+/// Build it without `stack-guard` and `w-xor-x`, unless the started image fits the
+/// regions they lock.
+pub unsafe fn jump_to(image_base: usize) -> ! {
+    let header = unsafe { ImageHeader::read(image_base) }.expect("no image header at image_base");
+
+    unsafe {
+        // mstatus.MIE
+        asm!("csrc mstatus, {}", in(reg) 0x8usize);
+        #[cfg(has_gintenr)]
+        qingke::register::gintenr::write(0);
+
+        // a pending SysTick would fire as soon as the image enables interrupts
+        ptr::write_volatile(STK_CTLR, 0);
+        #[cfg(not(qingke_core = "v3a"))]
+        ptr::write_volatile(STK_SR, 0);
+
+        pfic::disable_all();
+        pfic::unpend_all();
+        for channel in 0..pfic::VTF_CHANNELS {
+            pfic::disable_vtf(channel);
+        }
+
+        // hardware stack and nesting off
+        #[cfg(not(hpe_depth = "0"))]
+        asm!("csrw 0x804, zero");
+
+        mtvec::write(0, TrapMode::Direct);
+
+        asm!(
+            "mv sp, {sp}",
+            "mv gp, {gp}",
+            "jr {entry}",
+            sp = in(reg) header.stack_top as usize,
+            gp = in(reg) header.global_pointer as usize,
+            entry = in(reg) header.entry as usize,
+            options(noreturn),
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Crash record kept across a reset
+//!
+//! With the `crash-record` feature, the default exception handler, the default
+//! `StackOverflow` handler and the panic handler write a [`CrashRecord`] to `.uninit`
+//! and reset the chip. After the reset, [`take_last`] returns it once:
+//!
+//! ```ignore
+//! if let Some(crash) = qingke_rt::crash::take_last() {
+//!     // log or send it, e.g. as bytes for `qingke-crashdump`
+//!     uart.write(crash.as_bytes());
+//! }
+//! ```
+//!
+//! The record has a fixed little-endian layout, `VERSION` changes with it. It ends
+//! with the CRC-32 (IEEE) of the bytes before it, so the random content of RAM after
+//! power-on is not mistaken for a record.
+use core::fmt::{self, Write};
+use core::mem::{self, MaybeUninit};
+use core::panic::PanicInfo;
+use core::ptr;
+
+use qingke::pfic;
+
+use crate::TrapFrame;
+
+/// `"QKCR"`, first word of the record
+pub const MAGIC: u32 = 0x5243_4b51;
+/// Layout version
+pub const VERSION: u32 = 1;
+/// Words of stack saved from `sp` up
+pub const STACK_WORDS: usize = 16;
+/// Bytes of the panic message kept, the rest is cut
+pub const MESSAGE_LEN: usize = 128;
+
+/// Interrupt global status register, nesting and active state
+const PFIC_GISR: *const u32 = 0xE000_E04C as *const u32;
+
+/// What reset the chip
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Kind {
+    /// An exception without handler, `mcause`, `mepc` and `mtval` are set
+    Exception = 0,
+    /// A panic, with its location and message
+    Panic = 1,
+    /// The stack ran into the `stack-guard` region, `mepc` is the faulting pc
+    StackOverflow = 2,
+}
+
+/// State of the core when it crashed
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrashRecord {
+    magic: u32,
+    version: u32,
+    kind: u32,
+    pub mcause: u32,
+    pub mepc: u32,
+    pub mtval: u32,
+    pub sp: u32,
+    /// Return address at the exception, 0 for panics and stack overflows
+    pub ra: u32,
+    /// PFIC `GISR`: interrupt nesting level in bits 0 to 7, an interrupt active in bit 8
+    pub gisr: u32,
+    /// Words from `sp` up, zero past the top of the stack
+    pub stack: [u32; STACK_WORDS],
+    message_len: u32,
+    message: [u8; MESSAGE_LEN],
+    checksum: u32,
+}
+
+#[unsafe(link_section = ".uninit.qingke_rt.crash")]
+static mut RECORD: MaybeUninit<CrashRecord> = MaybeUninit::uninit();
+
+unsafe extern "C" {
+    static _stack_top: u8;
+}
+
+impl CrashRecord {
+    const EMPTY: Self = CrashRecord {
+        magic: MAGIC,
+        version: VERSION,
+        kind: 0,
+        mcause: 0,
+        mepc: 0,
+        mtval: 0,
+        sp: 0,
+        ra: 0,
+        gisr: 0,
+        stack: [0; STACK_WORDS],
+        message_len: 0,
+        message: [0; MESSAGE_LEN],
+        checksum: 0,
+    };
+
+    pub fn kind(&self) -> Kind {
+        match self.kind {
+            1 => Kind::Panic,
+            2 => Kind::StackOverflow,
+            _ => Kind::Exception,
+        }
+    }
+
+    /// Panic location and message, `file:line:column: message`, empty for other crashes
+    pub fn message(&self) -> &str {
+        let bytes = &self.message[..(self.message_len as usize).min(MESSAGE_LEN)];
+        // the cut can fall in the middle of a character
+        match core::str::from_utf8(bytes) {
+            Ok(message) => message,
+            Err(e) => unsafe { core::str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+        }
+    }
+
+    /// The raw record, as decoded by `qingke-crashdump`
+    pub fn as_bytes(&self) -> &[u8; mem::size_of::<CrashRecord>()] {
+        unsafe { &*(self as *const Self as *const [u8; mem::size_of::<CrashRecord>()]) }
+    }
+
+    fn checksum(&self) -> u32 {
+        crc32(&self.as_bytes()[..mem::offset_of!(CrashRecord, checksum)])
+    }
+}
+
+/// Bitwise CRC-32 (IEEE), small rather than fast
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Words of the record
+const WORDS: usize = mem::size_of::<CrashRecord>() / 4;
+
+/// The record of the last crash, if any, cleared so it is returned only once
+pub fn take_last() -> Option<CrashRecord> {
+    let slot = &raw mut RECORD;
+    // SAFETY: `.uninit` is not initialized at reset, its content is arbitrary bytes, read
+    // as integers, for which every bit pattern is valid. It only becomes a `CrashRecord`
+    // once magic, version and checksum match.
+    let words = unsafe { ptr::read_volatile(slot.cast::<[u32; WORDS]>()) };
+    let checksum_offset = mem::offset_of!(CrashRecord, checksum) / 4;
+    let bytes =
+        unsafe { core::slice::from_raw_parts(words.as_ptr().cast::<u8>(), checksum_offset * 4) };
+    if words[0] != MAGIC || words[1] != VERSION || words[checksum_offset] != crc32(bytes) {
+        return None;
+    }
+    // SAFETY: the words are a record written by `save`, and all its fields are integers
+    let record = unsafe { mem::transmute::<[u32; WORDS], CrashRecord>(words) };
+    unsafe { ptr::write_volatile(slot.cast::<u32>(), 0) };
+    Some(record)
+}
+
+/// Write the record started by `fill`, then reset
+fn save(kind: Kind, sp: usize, fill: impl FnOnce(&mut CrashRecord)) -> ! {
+    qingke::riscv::interrupt::disable();
+
+    // built in place, the stack may have little room left
+    let slot = (&raw mut RECORD).cast::<CrashRecord>();
+    unsafe { slot.write(CrashRecord::EMPTY) };
+    let record = unsafe { &mut *slot };
+    record.kind = kind as u32;
+    record.sp = sp as u32;
+    record.gisr = unsafe { ptr::read_volatile(PFIC_GISR) };
+
+    let top = &raw const _stack_top as usize;
+    if sp.is_multiple_of(4) {
+        for (i, word) in record.stack.iter_mut().enumerate() {
+            let address = sp + i * 4;
+            if address + 4 > top {
+                break;
+            }
+            *word = unsafe { ptr::read_volatile(address as *const u32) };
+        }
+    }
+
+    fill(record);
+    record.checksum = record.checksum();
+
+    pfic::system_reset()
+}
+
+pub(crate) fn exception(frame: &TrapFrame) -> ! {
+    save(Kind::Exception, frame.sp(), |record| {
+        record.mcause = frame.mcause as u32;
+        record.mepc = frame.mepc as u32;
+        record.mtval = frame.mtval as u32;
+        record.ra = frame.ra as u32;
+    })
+}
+
+pub(crate) fn stack_overflow(pc: usize) -> ! {
+    unsafe extern "C" {
+        static _stack_guard_end: u8;
+    }
+    // the stack was restarted at the top, the snapshot starts at the guard end
+    let sp = &raw const _stack_guard_end as usize;
+    save(Kind::StackOverflow, sp, |record| record.mepc = pc as u32)
+}
+
+struct Message<'a>(&'a mut CrashRecord);
+
+impl Write for Message<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = self.0.message_len as usize;
+        let len = s.len().min(MESSAGE_LEN - start);
+        self.0.message[start..start + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.0.message_len += len as u32;
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let sp: usize;
+    unsafe { core::arch::asm!("mv {}, sp", out(reg) sp) };
+    save(Kind::Panic, sp, |record| {
+        // the location first, so a long message does not cut it
+        let mut message = Message(record);
+        if let Some(location) = info.location() {
+            let _ = write!(message, "{location}: ");
+        }
+        let _ = write!(message, "{}", info.message());
+    })
+}
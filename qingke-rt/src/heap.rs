@@ -0,0 +1,74 @@
+//! Global allocator on the runtime heap region
+//!
+//! With the `alloc` feature a first-fit linked list allocator is set up on
+//! `_sheap.._eheap` before `main`. Reserve the heap with `_heap_size` in `memory.x`.
+//!
+//! When an allocation can not be satisfied, `AllocError` is called with the requested
+//! size and alignment. If it returns, the allocation fails and `alloc` panics.
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::RefCell;
+use core::ptr::{self, NonNull};
+
+use critical_section::Mutex;
+use linked_list_allocator::Heap;
+
+struct Allocator(Mutex<RefCell<Heap>>);
+
+#[global_allocator]
+static HEAP: Allocator = Allocator(Mutex::new(RefCell::new(Heap::empty())));
+
+unsafe extern "C" {
+    static _sheap: u8;
+    static _eheap: u8;
+
+    fn AllocError(size: usize, align: usize);
+}
+
+#[unsafe(link_section = ".init.rust")]
+pub(crate) unsafe fn init() {
+    let start = &raw const _sheap as usize;
+    let end = &raw const _eheap as usize;
+
+    // too small to hold the free list, leave the heap empty
+    if end - start < 2 * core::mem::size_of::<usize>() {
+        return;
+    }
+    critical_section::with(|cs| unsafe {
+        HEAP.0
+            .borrow_ref_mut(cs)
+            .init(start as *mut u8, end - start);
+    });
+}
+
+/// Bytes currently allocated
+pub fn used() -> usize {
+    critical_section::with(|cs| HEAP.0.borrow_ref(cs).used())
+}
+
+/// Bytes free for allocations, possibly fragmented
+pub fn free() -> usize {
+    critical_section::with(|cs| HEAP.0.borrow_ref(cs).free())
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let block =
+            critical_section::with(|cs| self.0.borrow_ref_mut(cs).allocate_first_fit(layout).ok());
+        match block {
+            Some(block) => block.as_ptr(),
+            None => {
+                // outside of the critical section, the hook may log or free caches
+                unsafe { AllocError(layout.size(), layout.align()) };
+                ptr::null_mut()
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        critical_section::with(|cs| unsafe {
+            self.0
+                .borrow_ref_mut(cs)
+                .deallocate(NonNull::new_unchecked(ptr), layout);
+        });
+    }
+}
@@ -10,40 +10,76 @@ use qingke::{
 };
 #[cfg(feature = "highcode")]
 pub use qingke_rt_macros::highcode;
-pub use qingke_rt_macros::{entry, interrupt};
-
-use core::arch::global_asm;
+#[cfg(feature = "u-mode")]
+pub use qingke_rt_macros::syscall;
+pub use qingke_rt_macros::{entry, exception, interrupt, pre_init};
 
 mod asm;
+pub mod boot;
+#[cfg(feature = "crash-record")]
+pub mod crash;
+#[cfg(feature = "alloc")]
+pub mod heap;
+#[cfg(feature = "stack-paint")]
+pub mod stack;
+#[cfg(feature = "stack-guard")]
+mod stack_guard;
+#[cfg(feature = "u-mode")]
+pub mod syscall;
+#[cfg(feature = "w-xor-x")]
+mod w_xor_x;
+
+#[cfg(all(feature = "u-mode", not(has_umode)))]
+compile_error!("The selected QingKe core has no user mode, disable the `u-mode` feature");
+
+#[cfg(all(feature = "w-xor-x", pmp_regions = "0"))]
+compile_error!("The selected QingKe core has no PMP, disable the `w-xor-x` feature");
+
+#[cfg(all(feature = "w-xor-x", feature = "highcode", feature = "stack-guard"))]
+compile_error!(
+    "`w-xor-x` with `highcode` takes every PMP entry, it can not be combined with `stack-guard`"
+);
+
+#[cfg(all(
+    feature = "w-xor-x",
+    feature = "highcode",
+    feature = "stack-at-ram-start"
+))]
+compile_error!(
+    "`w-xor-x` with `highcode` needs `.highcode` at the start of RAM, it can not be combined with `stack-at-ram-start`"
+);
 
 // Let this crate conflicts with riscv-rt
 #[unsafe(export_name = "error: riscv-rt appears more than once in the dependency graph")]
 #[doc(hidden)]
 pub static __ONCE__: () = ();
 
+// exception handlers get the `TrapFrame`, they can ignore it
 unsafe extern "C" {
     fn Exception();
 
-    fn InstructionMisaligned();
-    fn InstructionFault();
-    fn IllegalInstruction();
-    fn LoadMisaligned();
-    fn LoadFault();
-    fn StoreMisaligned();
-    fn StoreFault();
+    fn InstructionMisaligned(frame: &mut TrapFrame);
+    fn InstructionFault(frame: &mut TrapFrame);
+    fn IllegalInstruction(frame: &mut TrapFrame);
+    fn LoadMisaligned(frame: &mut TrapFrame);
+    fn LoadFault(frame: &mut TrapFrame);
+    fn StoreMisaligned(frame: &mut TrapFrame);
+    fn StoreFault(frame: &mut TrapFrame);
 
     fn NonMaskableInt();
-    fn MachineEnvCall();
-    fn UserEnvCall();
-    fn Breakpoint();
+    fn MachineEnvCall(frame: &mut TrapFrame);
+    fn UserEnvCall(frame: &mut TrapFrame);
+    fn Breakpoint(frame: &mut TrapFrame);
     fn SysTick();
     fn Software();
+
+    fn _exception_handler();
 }
 
 #[doc(hidden)]
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".vector_table.exceptions")]
-pub static __EXCEPTIONS: [Option<unsafe extern "C" fn()>; 12] = [
+pub static __EXCEPTIONS: [Option<unsafe extern "C" fn(&mut TrapFrame)>; 12] = [
     Some(InstructionMisaligned), // 0
     Some(InstructionFault),
     Some(IllegalInstruction),
@@ -86,22 +122,68 @@ impl CoreInterrupt {
     }
 }
 
-/// Core interrupts, without the first one
+/// Registers and trap CSRs of the code that raised an exception
+///
+/// Exception handlers may take it as argument, `extern "C" fn LoadFault(frame: &mut TrapFrame)`.
+/// On return, execution resumes at `mepc`: add 4 (or 2 for a compressed instruction) to skip
+/// the faulting instruction. The registers are reloaded from the frame too, but on cores with
+/// the hardware prologue/epilogue (V2, V4) `mret` restores them from the hardware stack, so
+/// changes to them only apply on V3.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrapFrame {
+    pub ra: usize,
+    pub t0: usize,
+    pub t1: usize,
+    pub t2: usize,
+    pub a0: usize,
+    pub a1: usize,
+    pub a2: usize,
+    pub a3: usize,
+    pub a4: usize,
+    pub a5: usize,
+    pub a6: usize,
+    pub a7: usize,
+    pub t3: usize,
+    pub t4: usize,
+    pub t5: usize,
+    pub t6: usize,
+    pub mcause: usize,
+    pub mepc: usize,
+    pub mtval: usize,
+    /// Read only, not written back
+    pub mstatus: usize,
+}
+
+impl TrapFrame {
+    pub fn cause(&self) -> mcause::Mcause {
+        mcause::Mcause::from_bits(self.mcause)
+    }
+
+    /// Stack pointer of the code that raised the exception, the frame sits just below it
+    pub fn sp(&self) -> usize {
+        self as *const Self as usize + core::mem::size_of::<Self>()
+    }
+}
+
+/// Core interrupts, from 2: entries 0 and 1 hold the reset jump at `_start`
 #[doc(hidden)]
 #[unsafe(no_mangle)]
 #[used]
 #[unsafe(link_section = ".vector_table.core_interrupts")]
-pub static __CORE_INTERRUPTS: [Option<unsafe extern "C" fn()>; 15] = [
+pub static __CORE_INTERRUPTS: [Option<unsafe extern "C" fn()>; 14] = [
     // None, // skip 0
-    None,
+    // None, // skip 1
     Some(NonMaskableInt), // 2
     Some(Exception),      // 3
     None,
-    Some(MachineEnvCall), // 5
+    Some(_exception_handler), // 5, MachineEnvCall
     None,
     None,
-    Some(UserEnvCall), // 8
-    Some(Breakpoint),  // 9
+    // exceptions with their own entry go through the exception handler as well, which
+    // dispatches them with `__EXCEPTIONS`, syscalls included
+    Some(_exception_handler), // 8, UserEnvCall
+    Some(_exception_handler), // 9, Breakpoint
     None,
     None,
     Some(SysTick), // 12
@@ -114,6 +196,9 @@ pub static __CORE_INTERRUPTS: [Option<unsafe extern "C" fn()>; 15] = [
 #[unsafe(link_section = ".init.rust")]
 #[unsafe(export_name = "_setup_interrupts")]
 unsafe extern "C" fn qingke_setup_interrupts() {
+    #[cfg(feature = "check-core")]
+    check_core();
+
     // enable hardware stack push
     // intsyscr(0x804): Open nested interrupts and hardware stack functions
     // 0x3 both nested interrupts and hardware stack
@@ -132,20 +217,17 @@ unsafe extern "C" fn qingke_setup_interrupts() {
 
     // Qingke V2A, V2C
     // (does not have user mode)
-    #[cfg(feature = "v2")]
+    #[cfg(not(has_umode))]
     unsafe {
         core::arch::asm!(
             "
             li t0, 0x1880
             csrw mstatus, t0
-            li t0, 0x3
-            csrw 0x804, t0
             "
         );
     }
 
-    // Qingke V3A, V3B, V3C, V3F, V3V
-    #[cfg(feature = "_v3")]
+    #[cfg(has_umode)]
     unsafe {
         #[cfg(feature = "u-mode")]
         core::arch::asm!(
@@ -165,48 +247,46 @@ unsafe extern "C" fn qingke_setup_interrupts() {
 
     // corecfgr(0xbc0): 流水线控制位 & 动态预测控制位
     // corecfgr(0xbc0): Pipeline control bit & Dynamic prediction control
-    #[cfg(any(
-        feature = "v4",
-        not(any(feature = "v2", feature = "_v3", feature = "v4"))     // Fallback condition
-    ))]
+    #[cfg(qingke_core = "v4")]
     unsafe {
-        #[cfg(feature = "u-mode")]
         core::arch::asm!(
             "
             li t0, 0x1f
             csrw 0xbc0, t0
-            li t0, 0x3
-            csrw 0x804, t0
-            li t0, 0x80
-            csrs mstatus, t0
             "
         );
-        #[cfg(not(feature = "u-mode"))]
+    }
+
+    #[cfg(not(hpe_depth = "0"))]
+    unsafe {
         core::arch::asm!(
             "
-            li t0, 0x1f
-            csrw 0xbc0, t0
             li t0, 0x3
             csrw 0x804, t0
-            li t0, 0x1880
-            csrs mstatus, t0
             "
         );
+    }
+
+    #[cfg(qingke_core = "v4")]
+    unsafe {
         qingke::register::gintenr::set_enable();
     }
 
     // V3A: no VectoredAddress support, use Direct mode + software dispatch.
-    #[cfg(feature = "v3a")]
+    #[cfg(not(has_vectored_address))]
     unsafe {
         unsafe extern "C" {
             fn _unified_trap_handler();
         }
-        mtvec::write(_unified_trap_handler as *const () as usize, TrapMode::Direct);
+        mtvec::write(
+            _unified_trap_handler as *const () as usize,
+            TrapMode::Direct,
+        );
     }
 
     // Qingke V2's mtvec must be 1KB aligned.
 
-    #[cfg(not(feature = "v3a"))]
+    #[cfg(has_vectored_address)]
     unsafe {
         #[cfg(feature = "highcode")]
         {
@@ -227,11 +307,62 @@ unsafe extern "C" fn qingke_setup_interrupts() {
         }
     }
 
+    #[cfg(feature = "stack-guard")]
+    unsafe {
+        stack_guard::setup();
+    }
+
+    #[cfg(feature = "w-xor-x")]
+    unsafe {
+        w_xor_x::setup();
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe {
+        heap::init();
+    }
+
+    #[cfg(feature = "u-mode")]
+    syscall::check_unique();
+
     unsafe {
         qingke::pfic::wfi_to_wfe(true);
     }
 }
 
+/// Trap into `_core_mismatch` if the image was built for another core, before
+/// any QingKe specific CSR is touched
+#[cfg(feature = "check-core")]
+#[unsafe(link_section = ".init.rust")]
+fn check_core() {
+    if !qingke::core_info().version.matches_build() {
+        unsafe extern "C" {
+            fn _core_mismatch();
+        }
+        unsafe {
+            mtvec::write(_core_mismatch as *const () as usize, TrapMode::Direct);
+            core::arch::asm!("ebreak");
+        }
+    }
+}
+
+// Parks the core with mcause = Breakpoint and mepc in `check_core`
+#[cfg(feature = "check-core")]
+core::arch::global_asm!(
+    r#"
+        .section .init.rust, "ax"
+        .global _core_mismatch
+        .align 2
+    _core_mismatch:
+        j _core_mismatch
+    "#
+);
+
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+#[allow(non_snake_case)]
+pub fn DefaultPreInit() {}
+
 #[doc(hidden)]
 #[unsafe(no_mangle)]
 #[allow(non_snake_case)]
@@ -246,6 +377,7 @@ pub fn DefaultInterruptHandler() {
 #[doc(hidden)]
 #[unsafe(no_mangle)]
 #[allow(non_snake_case)]
+#[cfg(not(feature = "crash-record"))]
 pub fn DefaultExceptionHandler() -> ! {
     loop {
         // Prevent this from turning into a UDF instruction
@@ -254,32 +386,61 @@ pub fn DefaultExceptionHandler() -> ! {
     }
 }
 
-// override _start_trap in riscv-rt
-global_asm!(
-    r#"
-        .section .trap, "ax"
-        .global _exception_handler
-    _exception_handler:
-        addi sp, sp, -4
-        sw ra, 0(sp)
-        jal _exception_handler_rust
-        lw ra, 0(sp)
-        addi sp, sp, 4
-        mret
-    "#
-);
+/// Saves a crash record and resets
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+#[allow(non_snake_case)]
+#[cfg(feature = "crash-record")]
+pub extern "C" fn DefaultExceptionHandler(frame: &mut TrapFrame) -> ! {
+    crash::exception(frame)
+}
+
+/// Called with the faulting pc when the stack runs into the guard region
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+#[allow(non_snake_case)]
+#[cfg(not(feature = "crash-record"))]
+pub extern "C" fn DefaultStackOverflow(_pc: usize) -> ! {
+    loop {
+        // Prevent this from turning into a UDF instruction
+        // see rust-lang/rust#28728 for details
+        continue;
+    }
+}
+
+/// Called with the faulting pc when the stack runs into the guard region, saves a
+/// crash record and resets
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+#[allow(non_snake_case)]
+#[cfg(feature = "crash-record")]
+pub extern "C" fn DefaultStackOverflow(pc: usize) -> ! {
+    crash::stack_overflow(pc)
+}
+
+/// Called with the requested size and alignment when the heap can not satisfy an allocation
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+#[allow(non_snake_case)]
+#[cfg(feature = "alloc")]
+pub extern "C" fn DefaultAllocError(_size: usize, _align: usize) {}
 
 // V3A software dispatch handler for Direct mode.
 // Reads mcause, looks up handler address from the vector table, and jumps to it.
-#[cfg(all(feature = "v3a", feature = "highcode"))]
-global_asm!(
+#[cfg(all(not(has_vectored_address), feature = "highcode"))]
+core::arch::global_asm!(
     r#"
         .section .trap, "ax"
         .global _unified_trap_handler
         .align 2
     _unified_trap_handler:
+        csrw mscratch, t0
         csrr t0, mcause
-        bgez t0, _exception_handler
+        bltz t0, 2f
+        // exceptions get the trapping code's t0 back, for the trap frame
+        csrr t0, mscratch
+        j _exception_handler
+    2:
         slli t0, t0, 1
         srli t0, t0, 1
         slli t0, t0, 2
@@ -294,15 +455,20 @@ global_asm!(
     "#
 );
 
-#[cfg(all(feature = "v3a", not(feature = "highcode")))]
-global_asm!(
+#[cfg(all(not(has_vectored_address), not(feature = "highcode")))]
+core::arch::global_asm!(
     r#"
         .section .trap, "ax"
         .global _unified_trap_handler
         .align 2
     _unified_trap_handler:
+        csrw mscratch, t0
         csrr t0, mcause
-        bgez t0, _exception_handler
+        bltz t0, 2f
+        // exceptions get the trapping code's t0 back, for the trap frame
+        csrr t0, mscratch
+        j _exception_handler
+    2:
         slli t0, t0, 1
         srli t0, t0, 1
         slli t0, t0, 2
@@ -319,25 +485,31 @@ global_asm!(
 #[doc(hidden)]
 #[unsafe(link_section = ".trap.rust")]
 #[unsafe(export_name = "_exception_handler_rust")]
-pub unsafe extern "C" fn qingke_exception_handler() {
+pub unsafe extern "C" fn qingke_exception_handler(frame: &mut TrapFrame) {
     // jump according to the __EXCEPTIONS table
     unsafe extern "C" {
-        fn ExceptionHandler();
+        fn ExceptionHandler(frame: &mut TrapFrame);
     }
 
-    let cause = mcause::read();
+    let cause = frame.cause();
     let code = cause.code();
 
+    #[cfg(feature = "u-mode")]
+    if cause.is_exception() && code == CoreInterrupt::UserEnvCall as usize {
+        unsafe { syscall::dispatch(frame) };
+        return;
+    }
+
     if cause.is_exception() {
         if code < __EXCEPTIONS.len() {
             let h = &__EXCEPTIONS[code];
             if let Some(handler) = h {
-                unsafe { handler() };
+                unsafe { handler(frame) };
             } else {
-                unsafe { ExceptionHandler() };
+                unsafe { ExceptionHandler(frame) };
             }
         } else {
-            unsafe { ExceptionHandler() };
+            unsafe { ExceptionHandler(frame) };
         }
     } else {
         loop {
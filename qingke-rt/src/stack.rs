@@ -0,0 +1,46 @@
+//! Stack usage measurement
+//!
+//! With the `stack-paint` feature the reset code fills the stack, from `_stack_bottom`
+//! to `_stack_top`, with [`PAINT`]. Scanning for the lowest overwritten
+//! word tells how deep the stack has ever grown.
+use core::ptr;
+
+/// Pattern written at reset, must match `asm.rs`
+pub const PAINT: u32 = 0xcccc_cccc;
+
+unsafe extern "C" {
+    static _stack_bottom: u32;
+    static _stack_guard_end: u32;
+    static _stack_top: u32;
+}
+
+/// Lowest address the stack can grow to
+#[inline]
+pub fn bottom() -> usize {
+    // reading the guard region traps
+    if cfg!(feature = "stack-guard") {
+        &raw const _stack_guard_end as usize
+    } else {
+        &raw const _stack_bottom as usize
+    }
+}
+
+/// Initial stack pointer
+#[inline]
+pub fn top() -> usize {
+    &raw const _stack_top as usize
+}
+
+/// Bytes between the stack bottom and the deepest stack use, never written since reset
+pub fn free_bytes() -> usize {
+    let mut p = bottom();
+    while p < top() && unsafe { ptr::read_volatile(p as *const u32) } == PAINT {
+        p += 4;
+    }
+    p - bottom()
+}
+
+/// Most stack used since reset, in bytes
+pub fn high_water_mark() -> usize {
+    top() - bottom() - free_bytes()
+}
@@ -0,0 +1,49 @@
+//! Stack overflow guard
+//!
+//! A no-access region at the bottom of the stack, from `_stack_guard_start` to
+//! `_stack_guard_end`. Cores with PMP lock entry 0 over it, so it applies in M-mode
+//! too; V2 chains two debug triggers instead. Any access to it traps, and
+//! `_exception_handler` calls `StackOverflow` with the faulting pc on a fresh stack.
+#[cfg(pmp_regions = "0")]
+use core::arch::asm;
+
+unsafe extern "C" {
+    static _stack_guard_start: u8;
+    static _stack_guard_end: u8;
+}
+
+#[unsafe(link_section = ".init.rust")]
+pub(crate) unsafe fn setup() {
+    let start = &raw const _stack_guard_start as usize;
+    let end = &raw const _stack_guard_end as usize;
+
+    // NAPOT entry 0: locked, no R/W/X
+    #[cfg(not(pmp_regions = "0"))]
+    unsafe {
+        qingke::pmp::set(0, qingke::pmp::Region::napot(start, end - start).locked());
+    }
+
+    // mcontrol: type 2, breakpoint exception, M-mode, load and store;
+    // trigger 0 matches `>= start` and is chained with trigger 1 `< end`.
+    // Experimental: not verified on hardware
+    #[cfg(pmp_regions = "0")]
+    unsafe {
+        const MCONTROL: usize = (2 << 28) | (1 << 6) | (1 << 1) | (1 << 0);
+        const CHAIN: usize = 1 << 11;
+        const MATCH_GE: usize = 2 << 7;
+        const MATCH_LT: usize = 3 << 7;
+        asm!(
+            "csrw tselect, zero",
+            "csrw tdata2, {start}",
+            "csrw tdata1, {ge}",
+            "csrw tselect, {one}",
+            "csrw tdata2, {end}",
+            "csrw tdata1, {lt}",
+            start = in(reg) start,
+            end = in(reg) end,
+            one = in(reg) 1usize,
+            ge = in(reg) MCONTROL | CHAIN | MATCH_GE,
+            lt = in(reg) MCONTROL | MATCH_LT,
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! System calls from user mode
+//!
+//! With the `u-mode` feature `main` runs in U-mode. It asks for privileged work with
+//! [`syscall!`](crate::syscall::syscall), which `ecall`s into the `UserEnvCall` exception,
+//! where the handler registered with [`#[syscall(NUMBER)]`](crate::syscall) runs in
+//! M-mode:
+//!
+//! ```ignore
+//! const READ_TEMPERATURE: usize = 1;
+//!
+//! #[qingke_rt::syscall(READ_TEMPERATURE)]
+//! fn read_temperature(channel: usize) -> usize {
+//!     adc_read(channel)
+//! }
+//!
+//! // in U-mode
+//! let t = qingke_rt::syscall::syscall!(READ_TEMPERATURE, 0);
+//! ```
+//!
+//! A number without a handler returns [`UNKNOWN_SYSCALL`]:
+//!
+//! ```ignore
+//! const SELF_TEST: usize = 7;
+//!
+//! // no `#[syscall(SELF_TEST)]` in this build
+//! if qingke_rt::syscall::syscall!(SELF_TEST) == qingke_rt::syscall::UNKNOWN_SYSCALL {
+//!     // fall back, or report the missing handler
+//! }
+//! ```
+//!
+//! Each number has one handler, two handlers for the same number panic at startup.
+//!
+//! The number and up to 4 arguments are passed in a request on the caller's stack,
+//! and the result is written back there: the hardware prologue/epilogue would
+//! overwrite a result left in `a0`.
+use core::arch::asm;
+use core::mem;
+
+use crate::TrapFrame;
+
+/// Result of a syscall without a registered handler
+pub const UNKNOWN_SYSCALL: usize = usize::MAX;
+
+/// Entry of the `.syscalls` table, emitted by `#[syscall]`
+#[doc(hidden)]
+#[repr(C)]
+pub struct SyscallEntry {
+    pub number: usize,
+    pub handler: extern "C" fn(&[usize; 4]) -> usize,
+}
+
+/// Request built on the caller's stack, its address is passed in `a0`
+#[repr(C)]
+struct Request {
+    number: usize,
+    args: [usize; 4],
+    ret: usize,
+}
+
+unsafe extern "C" {
+    static __ssyscalls: SyscallEntry;
+    static __esyscalls: SyscallEntry;
+
+    static _stack_bottom: u8;
+    static _stack_top: u8;
+}
+
+/// The `.syscalls` table
+fn entries() -> &'static [SyscallEntry] {
+    let start = &raw const __ssyscalls;
+    let end = &raw const __esyscalls;
+    unsafe { core::slice::from_raw_parts(start, end.offset_from(start) as usize) }
+}
+
+/// Panic if two handlers have the same number: dispatch would only ever run the first
+/// one in link order
+#[unsafe(link_section = ".init.rust")]
+pub(crate) fn check_unique() {
+    let entries = entries();
+    for (i, entry) in entries.iter().enumerate() {
+        if entries[i + 1..]
+            .iter()
+            .any(|other| other.number == entry.number)
+        {
+            panic!("more than one `#[syscall({})]` handler", entry.number);
+        }
+    }
+}
+
+/// Call the handler of syscall `number` in M-mode, returns its result or
+/// [`UNKNOWN_SYSCALL`]. Use [`syscall!`].
+///
+/// Must be called in U-mode: in M-mode, `ecall` raises `MachineEnvCall` instead.
+#[inline(never)]
+pub fn invoke(number: usize, args: [usize; 4]) -> usize {
+    let mut request = Request {
+        number,
+        args,
+        ret: UNKNOWN_SYSCALL,
+    };
+    unsafe { asm!("ecall", in("a0") &raw mut request, options(nostack)) };
+    request.ret
+}
+
+/// Run the handler for the request whose address is in the `a0` of the `ecall`
+pub(crate) unsafe fn dispatch(frame: &mut TrapFrame) {
+    let request = frame.a0;
+    // only requests on the stack, as built by `invoke`: U-mode must not make
+    // M-mode write anywhere else
+    let stack = (&raw const _stack_bottom as usize)..(&raw const _stack_top as usize);
+    let valid = request.is_multiple_of(mem::align_of::<Request>())
+        && stack.contains(&request)
+        && request + mem::size_of::<Request>() <= stack.end;
+
+    if valid {
+        let request = unsafe { &mut *(request as *mut Request) };
+        request.ret = entries()
+            .iter()
+            .find(|entry| entry.number == request.number)
+            .map_or(UNKNOWN_SYSCALL, |entry| (entry.handler)(&request.args));
+    }
+
+    // resume after the `ecall`
+    frame.mepc += 4;
+}
+
+/// Call a syscall registered with `#[syscall(NUMBER)]`, with up to 4 `usize` arguments
+///
+/// ```ignore
+/// let result = qingke_rt::syscall::syscall!(NUMBER, arg0, arg1);
+/// ```
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __syscall {
+    ($number:expr) => {
+        $crate::syscall::invoke($number, [0; 4])
+    };
+    ($number:expr, $a0:expr) => {
+        $crate::syscall::invoke($number, [$a0 as usize, 0, 0, 0])
+    };
+    ($number:expr, $a0:expr, $a1:expr) => {
+        $crate::syscall::invoke($number, [$a0 as usize, $a1 as usize, 0, 0])
+    };
+    ($number:expr, $a0:expr, $a1:expr, $a2:expr) => {
+        $crate::syscall::invoke($number, [$a0 as usize, $a1 as usize, $a2 as usize, 0])
+    };
+    ($number:expr, $a0:expr, $a1:expr, $a2:expr, $a3:expr) => {
+        $crate::syscall::invoke(
+            $number,
+            [$a0 as usize, $a1 as usize, $a2 as usize, $a3 as usize],
+        )
+    };
+}
+
+// `syscall` at the crate root is the `#[syscall]` attribute
+#[doc(inline)]
+pub use crate::__syscall as syscall;
@@ -0,0 +1,72 @@
+//! W^X memory policy
+//!
+//! Locked PMP entries, so they apply in M-mode too, derived from the linker symbols:
+//! flash and `.highcode` are executable and read-only, RAM is writable and not
+//! executable. Other regions, peripherals and `QINGKE_RT_EXTRA_RAM` regions
+//! included, are left unmatched.
+//!
+//! The whole of RAM is data, not only `.data` to `.bss` and the stack: the heap and
+//! free RAM are written too, and 4 entries leave no room for finer regions.
+//!
+//! Without `highcode`, entries 1 and 2 hold flash and RAM, each rounded up to a power
+//! of 2, entry 0 is left to `stack-guard`. The linker script checks that the rounded
+//! regions stay aligned. With `highcode`, TOR entries split the address space at the
+//! end of flash, the start of RAM, where `.highcode` must start, and the end of
+//! `.highcode`. Between flash and RAM, system flash and option bytes are matched by
+//! an unlocked entry without permissions: no access from U-mode, M-mode keeps its
+//! default access.
+use qingke::pmp::{Config, Region};
+
+unsafe extern "C" {
+    static __flash_start: u8;
+    static __flash_end: u8;
+    static __ram_start: u8;
+    static __ram_end: u8;
+
+    #[cfg(feature = "highcode")]
+    static _highcode_vma_end: u8;
+}
+
+#[unsafe(link_section = ".init.rust")]
+pub(crate) unsafe fn setup() {
+    let flash_end = &raw const __flash_end as usize;
+    let ram_end = &raw const __ram_end as usize;
+
+    // TOR entries, each from the end of the previous one
+    #[cfg(feature = "highcode")]
+    let config = Config::new()
+        .region(0, Region::tor(flash_end).read().execute().locked())
+        .region(1, Region::tor(&raw const __ram_start as usize))
+        .region(
+            2,
+            Region::tor(&raw const _highcode_vma_end as usize)
+                .read()
+                .execute()
+                .locked(),
+        )
+        .region(3, Region::tor(ram_end).read().write().locked());
+
+    // the asserts of `Region::napot` hold, the linker script checks the alignment
+    #[cfg(not(feature = "highcode"))]
+    let config = {
+        let flash_start = &raw const __flash_start as usize;
+        let ram_start = &raw const __ram_start as usize;
+        Config::new()
+            .region(
+                1,
+                Region::napot(flash_start, (flash_end - flash_start).next_power_of_two())
+                    .read()
+                    .execute()
+                    .locked(),
+            )
+            .region(
+                2,
+                Region::napot(ram_start, (ram_end - ram_start).next_power_of_two())
+                    .read()
+                    .write()
+                    .locked(),
+            )
+    };
+
+    unsafe { config.apply() };
+}
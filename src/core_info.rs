@@ -0,0 +1,133 @@
+//! Core identification from `marchid`, `mimpid` and `misa`
+use core::arch::asm;
+
+/// QingKe core version, decoded from `marchid`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CoreVersion {
+    V2A,
+    V2C,
+    V3A,
+    V3B,
+    V3C,
+    V3F,
+    V4A,
+    V4B,
+    V4C,
+    V4F,
+    /// A WCH core not listed above, e.g. `V4J` is `Other { version: 4, serial: 'J' }`
+    Other {
+        version: u8,
+        serial: char,
+    },
+    /// `marchid` is not implemented (reads 0) or not in WCH's format
+    Unknown(u32),
+}
+
+impl CoreVersion {
+    /// Decode `marchid`
+    ///
+    /// WCH packs "WCH-V4F" as 5-bit letters (A = 1): vendor in [30:26], [25:21], [20:16],
+    /// `V` in [14:10], the version number in [9:5] and the serial letter in [4:0].
+    pub fn from_marchid(marchid: u32) -> Self {
+        let letter = |lsb: u32| (marchid >> lsb) & 0x1f;
+        let is_wch = marchid & (1 << 31) != 0
+            && letter(26) == 23 // W
+            && letter(21) == 3 // C
+            && letter(16) == 8 // H
+            && letter(10) == 22; // V
+        if !is_wch {
+            return CoreVersion::Unknown(marchid);
+        }
+
+        let version = letter(5) as u8;
+        let serial = (b'@' + letter(0) as u8) as char;
+        match (version, serial) {
+            (2, 'A') => CoreVersion::V2A,
+            (2, 'C') => CoreVersion::V2C,
+            (3, 'A') => CoreVersion::V3A,
+            (3, 'B') => CoreVersion::V3B,
+            (3, 'C') => CoreVersion::V3C,
+            (3, 'F') => CoreVersion::V3F,
+            (4, 'A') => CoreVersion::V4A,
+            (4, 'B') => CoreVersion::V4B,
+            (4, 'C') => CoreVersion::V4C,
+            (4, 'F') => CoreVersion::V4F,
+            (version, serial) => CoreVersion::Other { version, serial },
+        }
+    }
+
+    /// Major version, `None` if unknown
+    pub fn version(&self) -> Option<u8> {
+        match self {
+            CoreVersion::V2A | CoreVersion::V2C => Some(2),
+            CoreVersion::V3A | CoreVersion::V3B | CoreVersion::V3C | CoreVersion::V3F => Some(3),
+            CoreVersion::V4A | CoreVersion::V4B | CoreVersion::V4C | CoreVersion::V4F => Some(4),
+            CoreVersion::Other { version, .. } => Some(*version),
+            CoreVersion::Unknown(_) => None,
+        }
+    }
+
+    /// Whether this core can run code built for the selected core feature.
+    ///
+    /// An `Unknown` core can not be told apart and does not match.
+    pub fn matches_build(&self) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(qingke_core = "v2")] {
+                self.version() == Some(2)
+            } else if #[cfg(qingke_core = "v3a")] {
+                *self == CoreVersion::V3A
+            } else if #[cfg(qingke_core = "v3b")] {
+                self.version() == Some(3) && *self != CoreVersion::V3A
+            } else {
+                self.version() == Some(4)
+            }
+        }
+    }
+}
+
+/// ISA extensions from `misa`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Extensions(u32);
+
+impl Extensions {
+    /// Returns the extension bits, bit 0 is `A`
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether the extension letter is set, e.g. `contains('F')`
+    #[inline]
+    pub fn contains(&self, extension: char) -> bool {
+        let extension = extension.to_ascii_uppercase();
+        extension.is_ascii_uppercase() && self.0 & (1 << (extension as u32 - 'A' as u32)) != 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CoreInfo {
+    pub version: CoreVersion,
+    pub extensions: Extensions,
+    pub marchid: u32,
+    pub mimpid: u32,
+}
+
+/// Identify the running core
+pub fn core_info() -> CoreInfo {
+    let (marchid, mimpid, misa): (usize, usize, usize);
+    unsafe {
+        asm!("csrr {}, marchid", out(reg) marchid);
+        asm!("csrr {}, mimpid", out(reg) mimpid);
+        asm!("csrr {}, misa", out(reg) misa);
+    }
+
+    CoreInfo {
+        version: CoreVersion::from_marchid(marchid as u32),
+        extensions: Extensions(misa as u32 & 0x03ff_ffff),
+        marchid: marchid as u32,
+        mimpid: mimpid as u32,
+    }
+}
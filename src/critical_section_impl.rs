@@ -1,4 +1,4 @@
-use critical_section::{set_impl, Impl, RawRestoreState};
+use critical_section::{Impl, RawRestoreState, set_impl};
 
 struct SingleHartCriticalSection;
 set_impl!(SingleHartCriticalSection);
@@ -6,14 +6,14 @@ set_impl!(SingleHartCriticalSection);
 unsafe impl Impl for SingleHartCriticalSection {
     unsafe fn acquire() -> RawRestoreState {
         cfg_if::cfg_if! {
-            if #[cfg(any(feature = "v2", feature = "v3a"))] {
+            if #[cfg(not(has_gintenr))] {
                 // CH32V003 (qingke_v2) does not have gintenr register
                 // v3a has "invalid" gintenr register - according to QingKeV3_Processor_Manual.pdf page 26
                 // Use standard RISC-V mstatus.MIE instead
                 let mut mstatus: usize;
                 unsafe { core::arch::asm!("csrrci {}, mstatus, 0b1000", out(reg) mstatus) };
                 (mstatus & 0b1000) != 0
-            } else if #[cfg(feature = "v4")] {
+            } else if #[cfg(qingke_core = "v4")] {
                 // V4: mask MIE+MPIE together (0x88), matching openwch SDK
                 // https://github.com/openwch/ch32v20x/blob/main/EVT/EXAM/SRC/Core/core_riscv.h.
                 // Fixes silent MIE-stuck-at-0 wedge when critical_section is
@@ -22,7 +22,7 @@ unsafe impl Impl for SingleHartCriticalSection {
                 unsafe { core::arch::asm!("csrrc {}, 0x800, {}", out(reg) prior, in(reg) 0x88usize) };
                 (prior & 0x8) != 0
             } else {
-                // Other QingKe cores have gintenr register
+                // V3B
                 use crate::register::gintenr;
                 (gintenr::set_disable() & 0x8) != 0
             }
@@ -33,9 +33,9 @@ unsafe impl Impl for SingleHartCriticalSection {
         // Only re-enable interrupts if they were enabled before the critical section.
         if irq_state {
             cfg_if::cfg_if! {
-                if #[cfg(any(feature = "v2", feature = "v3a"))] {
+                if #[cfg(not(has_gintenr))] {
                     unsafe { core::arch::asm!("csrsi mstatus, 0b1000") };
-                } else if #[cfg(feature = "v4")] {
+                } else if #[cfg(qingke_core = "v4")] {
                     unsafe { core::arch::asm!("csrs 0x800, {}", in(reg) 0x88usize) };
                 } else {
                     use crate::register::gintenr;
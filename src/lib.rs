@@ -4,11 +4,18 @@
 #[macro_use]
 mod macros;
 
+pub mod core_info;
 pub mod interrupt;
 pub mod pfic;
+#[cfg(not(pmp_regions = "0"))]
+pub mod pmp;
 pub mod register;
 
+pub use core_info::core_info;
+
 // re-export
+#[cfg(feature = "portable-atomic")]
+pub use portable_atomic;
 pub use riscv;
 
 #[cfg(all(
@@ -25,7 +32,9 @@ pub use riscv;
 compile_error!(
     "As tested on QingKe V4, most likely the atomics are broken, 
 please validate the atomic instruction on the hardware using 
-something like litmus test suite before trusting them."
+the `qingke-litmus` test firmware before trusting them.
+Build for riscv32imc and enable the `portable-atomic` feature 
+to get atomics emulated by critical sections instead."
 );
 
 #[cfg(feature = "critical-section-impl")]
@@ -20,9 +20,20 @@ const PFIC_CFGR: *mut u32 = 0xE000E048 as *mut u32;
 /// 中断全局状态寄存器
 const PFIC_GISR: *mut u32 = 0xE000E04C as *mut u32;
 
-// /// VTF ID configure register
-// /// 免表中断 ID, 8-bit for each entry, max 4 entries
-// const PFIC_VTFIDR: *mut u32 = 0xE000E050 as *mut u32;
+/// Number of VTF channels
+#[cfg(qingke_core = "v2")]
+pub const VTF_CHANNELS: u8 = 2;
+/// Number of VTF channels
+#[cfg(not(qingke_core = "v2"))]
+pub const VTF_CHANNELS: u8 = 4;
+
+/// VTF base address register, V3
+/// 免表中断基地址寄存器
+const PFIC_VTFBADDRR: *mut u32 = 0xE000E044 as *mut u32;
+
+/// VTF ID configure register
+/// 免表中断 ID, 8-bit for each entry, max 4 entries
+const PFIC_VTFIDR: *mut u32 = 0xE000E050 as *mut u32;
 
 /// VTF interrupt x offset address register
 /// 免表中断地址寄存器
@@ -66,6 +77,28 @@ pub unsafe fn disable_interrupt(irq: u8) {
     }
 }
 
+/// Disable every interrupt
+///
+/// # Safety
+///
+/// Handlers relying on their interrupt stop being called.
+pub unsafe fn disable_all() {
+    for offset in 0..4 {
+        unsafe { ptr::write_volatile(PFIC_IRER0.offset(offset), 0xFFFF_FFFF) };
+    }
+}
+
+/// Clear every pending interrupt
+///
+/// # Safety
+///
+/// Pending events are lost.
+pub unsafe fn unpend_all() {
+    for offset in 0..4 {
+        unsafe { ptr::write_volatile(PFIC_IPRR0.offset(offset), 0xFFFF_FFFF) };
+    }
+}
+
 #[inline]
 pub fn is_enabled(irq: u8) -> bool {
     let offset = (irq / 32) as isize;
@@ -121,10 +154,9 @@ pub fn get_priority(irq: u8) -> u8 {
 }
 
 /// Enable VTF0, VTFBADDRR will be overwritten
-#[cfg(feature = "_v3")]
+#[cfg(not(has_vtf_id_register))]
 pub unsafe fn enable_vtf(channel: u8, irq: u8, address: u32) {
-    assert!(channel < 4, "VTF channel must be less than 4");
-    const PFIC_VTFBADDRR: *mut u32 = 0xE000E044 as *mut u32;
+    assert!(channel < VTF_CHANNELS, "VTF channel out of range");
 
     unsafe {
         ptr::write_volatile(PFIC_VTFBADDRR, address & 0xF000_0000);
@@ -136,22 +168,23 @@ pub unsafe fn enable_vtf(channel: u8, irq: u8, address: u32) {
     }
 }
 
-#[cfg(feature = "_v3")]
+#[cfg(not(has_vtf_id_register))]
 pub unsafe fn disable_vtf(channel: u8) {
-    assert!(channel < 4, "VTF channel must be less than 4");
-    let val = ptr::read_volatile(PFIC_VTFADDRR0.offset(channel as isize));
-    ptr::write_volatile(PFIC_VTFADDRR0.offset(channel as isize), val & 0x00FF_FFFF);
+    assert!(channel < VTF_CHANNELS, "VTF channel out of range");
+    unsafe {
+        let val = ptr::read_volatile(PFIC_VTFADDRR0.offset(channel as isize));
+        ptr::write_volatile(PFIC_VTFADDRR0.offset(channel as isize), val & 0x00FF_FFFF);
+    }
 }
 
-#[cfg(not(feature = "_v3"))]
+#[cfg(has_vtf_id_register)]
 pub unsafe fn enable_vtf(channel: u8, irq: u8, address: u32) {
-    assert!(channel < 4, "VTF channel must be less than 4");
+    assert!(channel < VTF_CHANNELS, "VTF channel out of range");
 
     // [31:24]: Numbering of VTF interrupt 3
     // [23:16]: Numbering of VTF interrupt 2
     // [15:8]: Numbering of VTF interrupt 1
     // [7:0]: Numbering of VTF interrupt 0
-    const PFIC_VTFIDR: *mut u32 = 0xE000E050 as *mut u32;
 
     unsafe {
         ptr::write_volatile(PFIC_VTFIDR, (irq as u32) << ((channel as u32) * 8));
@@ -163,9 +196,9 @@ pub unsafe fn enable_vtf(channel: u8, irq: u8, address: u32) {
     }
 }
 
-#[cfg(not(feature = "_v3"))]
+#[cfg(has_vtf_id_register)]
 pub unsafe fn disable_vtf(channel: u8) {
-    assert!(channel < 4, "VTF channel must be less than 4");
+    assert!(channel < VTF_CHANNELS, "VTF channel out of range");
     unsafe {
         let val = ptr::read_volatile(PFIC_VTFADDRR0.offset(channel as isize));
         ptr::write_volatile(PFIC_VTFADDRR0.offset(channel as isize), val & 0xFFFF_FFFE);
@@ -185,3 +218,102 @@ pub unsafe fn wfi_to_wfe(v: bool) {
         ptr::write_volatile(PFIC_SCTLR, val);
     });
 }
+
+/// Reset the whole chip, as the reset pin does
+#[cfg(not(any(qingke_core = "v3a", qingke_core = "v3b")))]
+pub fn system_reset() -> ! {
+    unsafe { ptr::write_volatile(PFIC_SCTLR, 1 << 31) };
+    loop {
+        continue;
+    }
+}
+
+/// Reset the whole chip, as the reset pin does
+#[cfg(any(qingke_core = "v3a", qingke_core = "v3b"))]
+pub fn system_reset() -> ! {
+    // KEYCODE 0xBEEF and RESETSYS
+    unsafe { ptr::write_volatile(PFIC_CFGR, (0xBEEF << 16) | (1 << 7)) };
+    loop {
+        continue;
+    }
+}
+
+/// PFIC configuration: enabled interrupts, priorities, threshold, VTF channels and `SCTLR`
+///
+/// Pending and active state is not part of it. Use it to hand over to another image, or
+/// to put the configuration back after reprogramming the controller, e.g. around low power.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PficState {
+    /// Enable bits of interrupts 0 to 127
+    pub enabled: [u32; 4],
+    /// Priority bytes of interrupts 0 to 127, four per word
+    pub priorities: [u32; 32],
+    pub threshold: u32,
+    /// `VTFADDRRx`
+    pub vtf_addresses: [u32; VTF_CHANNELS as usize],
+    /// `VTFIDR`, on cores without it (V3) the interrupt numbers are in `vtf_addresses`
+    #[cfg(has_vtf_id_register)]
+    pub vtf_ids: u32,
+    /// `VTFBADDRR`, the high nibble of the V3 VTF addresses
+    #[cfg(not(has_vtf_id_register))]
+    pub vtf_base: u32,
+    pub sctlr: u32,
+}
+
+/// `SCTLR` bits kept by [`PficState::restore`], without `SETEVENT` and `SYSRESET`
+const SCTLR_CONFIG_MASK: u32 = 0x1F;
+
+impl PficState {
+    /// Read the current configuration
+    pub fn save() -> Self {
+        unsafe {
+            let read = |reg: *mut u32, offset: isize| ptr::read_volatile(reg.offset(offset));
+            PficState {
+                enabled: core::array::from_fn(|i| read(PFIC_ISR0, i as isize)),
+                priorities: core::array::from_fn(|i| read(PFIC_IPRIOR0 as *mut u32, i as isize)),
+                threshold: read(PFIC_ITHRESDR, 0),
+                vtf_addresses: core::array::from_fn(|i| read(PFIC_VTFADDRR0, i as isize)),
+                #[cfg(has_vtf_id_register)]
+                vtf_ids: read(PFIC_VTFIDR, 0),
+                #[cfg(not(has_vtf_id_register))]
+                vtf_base: read(PFIC_VTFBADDRR, 0),
+                sctlr: read(PFIC_SCTLR, 0),
+            }
+        }
+    }
+
+    /// Write the configuration back
+    ///
+    /// Interrupts are disabled while priorities and VTF channels are written, then the
+    /// saved ones are enabled again.
+    ///
+    /// # Safety
+    ///
+    /// Enabled interrupts may fire right away, their handlers must be ready.
+    pub unsafe fn restore(&self) {
+        unsafe {
+            disable_all();
+
+            for (i, &word) in self.priorities.iter().enumerate() {
+                ptr::write_volatile((PFIC_IPRIOR0 as *mut u32).add(i), word);
+            }
+            ptr::write_volatile(PFIC_ITHRESDR, self.threshold);
+
+            #[cfg(has_vtf_id_register)]
+            ptr::write_volatile(PFIC_VTFIDR, self.vtf_ids);
+            #[cfg(not(has_vtf_id_register))]
+            ptr::write_volatile(PFIC_VTFBADDRR, self.vtf_base);
+            for (i, &address) in self.vtf_addresses.iter().enumerate() {
+                ptr::write_volatile(PFIC_VTFADDRR0.add(i), address);
+            }
+
+            let sctlr = ptr::read_volatile(PFIC_SCTLR) & !SCTLR_CONFIG_MASK;
+            ptr::write_volatile(PFIC_SCTLR, sctlr | (self.sctlr & SCTLR_CONFIG_MASK));
+
+            for (i, &bits) in self.enabled.iter().enumerate() {
+                ptr::write_volatile(PFIC_IENR0.add(i), bits);
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! PMP, Physical Memory Protection
+//!
+//! QingKe V3 and V4 have [`REGIONS`] PMP entries with a granularity of
+//! [`GRANULARITY`] bytes, V2 has none. A [`Config`] is usually a `const` table
+//! applied at startup:
+//!
+//! ```ignore
+//! use qingke::pmp::{Config, Region};
+//!
+//! const PMP: Config = Config::new()
+//!     // flash: read and execute
+//!     .region(1, Region::napot(0x0000_0000, 256 * 1024).read().execute())
+//!     // RAM: read and write
+//!     .region(2, Region::napot(0x2000_0000, 64 * 1024).read().write());
+//!
+//! #[qingke_rt::pre_init]
+//! unsafe fn setup_pmp() {
+//!     unsafe { PMP.apply() };
+//! }
+//! ```
+//!
+//! Entries are matched in order, the lowest matching entry decides. Unlocked entries
+//! only apply to U-mode, locked ones to M-mode too and can not be changed until reset.
+//! The `stack-guard` feature of `qingke-rt` locks entry 0.
+
+use core::arch::asm;
+
+/// Number of PMP entries
+#[cfg(pmp_regions = "4")]
+pub const REGIONS: usize = 4;
+
+/// Smallest region, in bytes, from the core model: G = 0 on V3 and V4
+#[cfg(pmp_granularity = "4")]
+pub const GRANULARITY: usize = 4;
+
+const R: u8 = 1 << 0;
+const W: u8 = 1 << 1;
+const X: u8 = 1 << 2;
+const A_TOR: u8 = 1 << 3;
+const A_NA4: u8 = 2 << 3;
+const A_NAPOT: u8 = 3 << 3;
+const L: u8 = 1 << 7;
+
+/// Address range and permissions of a PMP entry, with no access by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Region {
+    pmpaddr: usize,
+    cfg: u8,
+}
+
+impl Region {
+    /// `size` bytes from `base`, `size` a power of 2 of at least 8 bytes and `base`
+    /// aligned to it
+    pub const fn napot(base: usize, size: usize) -> Self {
+        assert!(
+            size.is_power_of_two() && size >= 2 * GRANULARITY,
+            "NAPOT region size must be a power of 2 of at least 8 bytes"
+        );
+        assert!(
+            base.is_multiple_of(size),
+            "NAPOT region base must be aligned to its size"
+        );
+        Region {
+            pmpaddr: (base | (size / 2 - 1)) >> 2,
+            cfg: A_NAPOT,
+        }
+    }
+
+    /// The 4 bytes at `base`
+    pub const fn na4(base: usize) -> Self {
+        assert!(
+            base.is_multiple_of(GRANULARITY),
+            "NA4 region base must be 4 bytes aligned"
+        );
+        Region {
+            pmpaddr: base >> 2,
+            cfg: A_NA4,
+        }
+    }
+
+    /// From the address of the previous entry (0 for entry 0) up to `end`, excluded
+    pub const fn tor(end: usize) -> Self {
+        assert!(
+            end.is_multiple_of(GRANULARITY),
+            "TOR region end must be 4 bytes aligned"
+        );
+        Region {
+            pmpaddr: end >> 2,
+            cfg: A_TOR,
+        }
+    }
+
+    /// Allow loads
+    pub const fn read(self) -> Self {
+        Region {
+            cfg: self.cfg | R,
+            ..self
+        }
+    }
+
+    /// Allow stores, needs [`read`](Self::read) as well
+    pub const fn write(self) -> Self {
+        Region {
+            cfg: self.cfg | W,
+            ..self
+        }
+    }
+
+    /// Allow instruction fetches
+    pub const fn execute(self) -> Self {
+        Region {
+            cfg: self.cfg | X,
+            ..self
+        }
+    }
+
+    /// Apply to M-mode too, and ignore writes to the entry until reset
+    pub const fn locked(self) -> Self {
+        Region {
+            cfg: self.cfg | L,
+            ..self
+        }
+    }
+
+    /// Value of `pmpaddrX`
+    pub const fn pmpaddr(&self) -> usize {
+        self.pmpaddr
+    }
+
+    /// Byte of `pmpcfgX`
+    pub const fn cfg(&self) -> u8 {
+        self.cfg
+    }
+}
+
+/// PMP entries to set, the others are left as they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    regions: [Option<Region>; REGIONS],
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    pub const fn new() -> Self {
+        Config {
+            regions: [None; REGIONS],
+        }
+    }
+
+    /// Set entry `index` to `region`
+    pub const fn region(mut self, index: usize, region: Region) -> Self {
+        assert!(index < REGIONS, "PMP entry out of range");
+        self.regions[index] = Some(region);
+        self
+    }
+
+    pub const fn regions(&self) -> &[Option<Region>; REGIONS] {
+        &self.regions
+    }
+
+    /// Write the entries
+    ///
+    /// # Safety
+    ///
+    /// Locked regions apply to the running code: it must keep access to its code,
+    /// stack and data.
+    pub unsafe fn apply(&self) {
+        for (index, region) in self.regions.iter().enumerate() {
+            if let Some(region) = region {
+                unsafe { set(index, *region) };
+            }
+        }
+    }
+}
+
+/// Set entry `index` to `region`, ignored by the hardware if the entry is locked
+///
+/// # Safety
+///
+/// A locked region applies to the running code: it must keep access to its code,
+/// stack and data.
+#[inline]
+pub unsafe fn set(index: usize, region: Region) {
+    assert!(index < REGIONS, "PMP entry out of range");
+    let shift = index * 8;
+    unsafe {
+        // off while the address changes
+        asm!("csrc pmpcfg0, {}", in(reg) 0xffusize << shift);
+        match index {
+            0 => asm!("csrw pmpaddr0, {}", in(reg) region.pmpaddr),
+            1 => asm!("csrw pmpaddr1, {}", in(reg) region.pmpaddr),
+            2 => asm!("csrw pmpaddr2, {}", in(reg) region.pmpaddr),
+            _ => asm!("csrw pmpaddr3, {}", in(reg) region.pmpaddr),
+        }
+        asm!("csrs pmpcfg0, {}", in(reg) (region.cfg as usize) << shift);
+    }
+}
+
+/// Turn entry `index` off, ignored by the hardware if the entry is locked
+///
+/// # Safety
+///
+/// In U-mode, accesses the entry allowed may fault afterwards.
+#[inline]
+pub unsafe fn clear(index: usize) {
+    assert!(index < REGIONS, "PMP entry out of range");
+    unsafe { asm!("csrc pmpcfg0, {}", in(reg) 0xffusize << (index * 8)) };
+}
+
+/// Whether entry `index` is locked
+#[inline]
+pub fn is_locked(index: usize) -> bool {
+    assert!(index < REGIONS, "PMP entry out of range");
+    let pmpcfg0: usize;
+    unsafe { asm!("csrr {}, pmpcfg0", out(reg) pmpcfg0) };
+    (pmpcfg0 >> (index * 8)) as u8 & L != 0
+}
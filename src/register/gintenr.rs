@@ -6,13 +6,13 @@
 //!
 //! Write 0x08 to enable global interrupt
 //!
-//! NOTE: This register is NOT available on v2 (CH32V003).
-//! Use mstatus.MIE instead for v2.
+//! NOTE: This register is NOT available on v2 (CH32V003), and does not work on v3a.
+//! Use mstatus.MIE instead, see `cfg(has_gintenr)`.
 
-#[cfg(not(feature = "v2"))]
+#[cfg(any(has_gintenr, qingke_core = "v3a"))]
 use core::arch::asm;
 
-#[cfg(not(feature = "v2"))]
+#[cfg(has_gintenr)]
 #[inline]
 pub fn read() -> usize {
     let ans: usize;
@@ -20,20 +20,57 @@ pub fn read() -> usize {
     ans
 }
 
-#[cfg(not(feature = "v2"))]
+#[cfg(has_gintenr)]
 #[inline]
 pub unsafe fn write(bits: usize) {
     unsafe { asm!("csrw 0x800, {}", in(reg) bits) };
 }
 
-#[cfg(not(feature = "v2"))]
+#[cfg(has_gintenr)]
 #[inline]
 pub unsafe fn set_enable() {
     let mask = 0x8;
     unsafe { asm!("csrs 0x800, {}", in(reg) mask) };
 }
 
-#[cfg(not(feature = "v2"))]
+#[cfg(has_gintenr)]
+#[inline]
+/// Disable interrupt and return the old `GINTENR` value
+pub fn set_disable() -> usize {
+    let prev: usize;
+    let mask = 0x8usize;
+    unsafe { asm!("csrrc {}, 0x800, {}", out(reg) prev, in(reg) mask) };
+    prev
+}
+
+// V3A: kept for one release, these were available before `has_gintenr`
+
+#[cfg(qingke_core = "v3a")]
+#[deprecated(note = "`GINTENR` does not mask interrupts on V3A, use `mstatus.MIE`")]
+#[inline]
+pub fn read() -> usize {
+    let ans: usize;
+    unsafe { asm!("csrr {}, 0x800", out(reg) ans) };
+    ans
+}
+
+#[cfg(qingke_core = "v3a")]
+#[deprecated(note = "`GINTENR` does not mask interrupts on V3A, use `mstatus.MIE`")]
+#[inline]
+pub unsafe fn write(bits: usize) {
+    unsafe { asm!("csrw 0x800, {}", in(reg) bits) };
+}
+
+#[cfg(qingke_core = "v3a")]
+#[deprecated(note = "`GINTENR` does not mask interrupts on V3A, use `mstatus.MIE`")]
+#[inline]
+pub unsafe fn set_enable() {
+    let mask = 0x8;
+    unsafe { asm!("csrs 0x800, {}", in(reg) mask) };
+}
+
+#[cfg(qingke_core = "v3a")]
+#[deprecated(note = "`GINTENR` does not mask interrupts on V3A, use `mstatus.MIE`")]
 #[inline]
 /// Disable interrupt and return the old `GINTENR` value
 pub fn set_disable() -> usize {